@@ -0,0 +1,47 @@
+// Darkens the scene by the light map. The quad is stretched over the whole
+// view of every camera, and each pixel looks its world position up in the map.
+#import bevy_sprite::mesh2d_view_bindings::view
+
+struct LightingMaterial {
+  // the world area the light map covers: bottom-left corner, then size
+  bounds: vec4<f32>,
+};
+
+@group(1) @binding(0) var<uniform> material: LightingMaterial;
+@group(1) @binding(1) var light_map: texture_2d<f32>;
+@group(1) @binding(2) var light_map_sampler: sampler;
+
+struct Vertex {
+  @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) world_position: vec2<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+  // the quad is two units across, so its corners land on the view's corners
+  let clip = vec4<f32>(vertex.position.xy, 0.0, 1.0);
+  let world = view.inverse_view_proj * clip;
+  var out: VertexOutput;
+  out.clip_position = clip;
+  out.world_position = world.xy / world.w;
+  return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  let uv = (in.world_position - material.bounds.xy) / material.bounds.zw;
+  // rows run from the top of the map down
+  let light = textureSampleLevel(
+    light_map,
+    light_map_sampler,
+    vec2<f32>(uv.x, 1.0 - uv.y),
+    0.0,
+  );
+  // everything outside the map is fully lit
+  let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+  return select(vec4<f32>(0.0), light, inside);
+}
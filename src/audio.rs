@@ -0,0 +1,341 @@
+use bevy::{asset::LoadState, audio::Volume, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  hud::StatusLine,
+  loading::{check_loading, LoadingAssets},
+  map::{MapData, MapHandle},
+  player::{Player, PlayerId},
+  state::{AppState, GameplaySet, RunEntity},
+  terrain::{FootstepMaterial, Ground},
+  tile::AnimationEvent,
+};
+
+/// Where the volume settings are written, relative to the working directory.
+/// On the web, the key they're kept under in the browser's local storage.
+const SETTINGS_PATH: &str = "settings.ron";
+/// How long music takes to fade between areas, in seconds.
+const CROSSFADE_SECONDS: f32 = 2.0;
+/// How much each press of the volume keys changes the master volume.
+const VOLUME_STEP: f32 = 0.1;
+
+/// Volumes from 0.0 to 1.0, kept between sessions.
+#[derive(
+  Resource, Clone, Debug, PartialEq, Reflect, Serialize, Deserialize,
+)]
+#[reflect(Resource)]
+pub struct AudioSettings {
+  pub master:  f32,
+  pub music:   f32,
+  pub effects: f32,
+}
+
+impl Default for AudioSettings {
+  fn default() -> Self {
+    AudioSettings {
+      master:  0.8,
+      music:   0.6,
+      effects: 1.0,
+    }
+  }
+}
+
+impl AudioSettings {
+  /// Reads the settings saved last session, if there are any.
+  pub fn load() -> Self {
+    let Some(text) = read_settings() else {
+      return AudioSettings::default();
+    };
+    ron::from_str(&text).unwrap_or_else(|e| {
+      error!("failed to read {SETTINGS_PATH}: {e}");
+      AudioSettings::default()
+    })
+  }
+
+  pub fn music_volume(&self) -> f32 { self.master * self.music }
+  pub fn effects_volume(&self) -> f32 { self.master * self.effects }
+}
+
+#[derive(Resource)]
+struct FootstepSounds {
+  grass: Handle<AudioSource>,
+  stone: Handle<AudioSource>,
+  ice:   Handle<AudioSource>,
+}
+
+impl FromWorld for FootstepSounds {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    FootstepSounds {
+      grass: asset_server.load("audio/footstep_grass.ogg"),
+      stone: asset_server.load("audio/footstep_stone.ogg"),
+      ice:   asset_server.load("audio/footstep_ice.ogg"),
+    }
+  }
+}
+
+impl FootstepSounds {
+  fn get(&self, material: FootstepMaterial) -> Handle<AudioSource> {
+    match material {
+      FootstepMaterial::Grass => self.grass.clone(),
+      FootstepMaterial::Stone => self.stone.clone(),
+      FootstepMaterial::Ice => self.ice.clone(),
+    }
+  }
+}
+
+/// The music of every area in the map, by path, kept loaded so a track can
+/// start as soon as a player walks into its area.
+#[derive(Resource, Default)]
+struct AreaMusic(HashMap<String, Handle<AudioSource>>);
+
+fn track_footstep_sounds(
+  sounds: Res<FootstepSounds>,
+  mut loading: ResMut<LoadingAssets>,
+) {
+  for sound in [&sounds.grass, &sounds.stone, &sounds.ice] {
+    loading.add(sound.clone().untyped());
+  }
+}
+
+/// Starts loading the music of every area once the map is in, holding the
+/// loading screen until it has loaded too.
+fn track_area_music(
+  maps: Res<Assets<MapData>>,
+  handle: Res<MapHandle>,
+  asset_server: Res<AssetServer>,
+  mut music: ResMut<AreaMusic>,
+  mut loading: ResMut<LoadingAssets>,
+) {
+  let Some(map) = maps.get(&handle.0) else {
+    return;
+  };
+  for area in &map.areas {
+    if music.0.contains_key(&area.music) {
+      continue;
+    }
+    let track = asset_server.load(area.music.clone());
+    loading.add(track.clone().untyped());
+    music.0.insert(area.music.clone(), track);
+  }
+}
+
+/// Whether `source` failed to load. Such a source never plays, so an entity
+/// waiting on it would never despawn.
+fn failed(asset_server: &AssetServer, source: &Handle<AudioSource>) -> bool {
+  asset_server.get_load_state(source) == Some(LoadState::Failed)
+}
+
+/// A music track fading towards `target` volume. Tracks that fade out are
+/// despawned.
+#[derive(Component)]
+struct MusicTrack {
+  path:   String,
+  fade:   f32,
+  target: f32,
+}
+
+fn volume_keys(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut settings: ResMut<AudioSettings>,
+  mut status: ResMut<StatusLine>,
+) {
+  let step = if keyboard_input.just_pressed(KeyCode::Minus) {
+    -VOLUME_STEP
+  } else if keyboard_input.just_pressed(KeyCode::Equals) {
+    VOLUME_STEP
+  } else {
+    return;
+  };
+  settings.master = (settings.master + step).clamp(0.0, 1.0);
+  status.show(format!("Volume {:.0}%", settings.master * 100.0));
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_settings() -> Option<String> {
+  std::fs::read_to_string(SETTINGS_PATH).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_settings(text: &str) -> Result<(), String> {
+  std::fs::write(SETTINGS_PATH, text).map_err(|e| e.to_string())
+}
+
+/// The browser's local storage, which stands in for the settings file on the
+/// web.
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+  web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_settings() -> Option<String> {
+  local_storage()?.get_item(SETTINGS_PATH).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_settings(text: &str) -> Result<(), String> {
+  local_storage()
+    .ok_or_else(|| "no local storage".to_string())?
+    .set_item(SETTINGS_PATH, text)
+    .map_err(|e| format!("{e:?}"))
+}
+
+fn save_audio_settings(settings: Res<AudioSettings>) {
+  if !settings.is_changed() || settings.is_added() {
+    return;
+  }
+  let result = ron::ser::to_string_pretty(&*settings, Default::default())
+    .map_err(|e| e.to_string())
+    .and_then(|text| write_settings(&text));
+  if let Err(e) = result {
+    error!("failed to save {SETTINGS_PATH}: {e}");
+  }
+}
+
+/// Plays a footstep on every "footstep" animation event, picked by the
+/// footstep material of the ground under the entity.
+fn play_footsteps(
+  mut commands: Commands,
+  mut events: EventReader<AnimationEvent>,
+  transforms: Query<&GlobalTransform>,
+  ground: Res<Ground>,
+  sounds: Res<FootstepSounds>,
+  settings: Res<AudioSettings>,
+  asset_server: Res<AssetServer>,
+) {
+  for event in events.read() {
+    if event.name != "footstep" {
+      continue;
+    }
+    let Ok(transform) = transforms.get(event.entity) else {
+      continue;
+    };
+    let Some(under) = ground.at(transform.translation().xy()) else {
+      continue;
+    };
+    let source = sounds.get(under.terrain().footstep);
+    if failed(&asset_server, &source) {
+      continue;
+    }
+    commands.spawn(AudioBundle {
+      source,
+      settings: PlaybackSettings::DESPAWN
+        .with_volume(Volume::new_absolute(settings.effects_volume())),
+    });
+  }
+}
+
+/// Fades in the music of the area the first player is in, and fades out the
+/// rest.
+fn choose_area_music(
+  mut commands: Commands,
+  players: Query<(&PlayerId, &GlobalTransform), With<Player>>,
+  maps: Res<Assets<MapData>>,
+  handle: Res<MapHandle>,
+  asset_server: Res<AssetServer>,
+  area_music: Res<AreaMusic>,
+  mut tracks: Query<&mut MusicTrack>,
+) {
+  let Some((_, leader)) = players.iter().min_by_key(|(id, _)| **id) else {
+    return;
+  };
+  let music = maps
+    .get(&handle.0)
+    .and_then(|map| map.area_at(leader.translation().xy()))
+    .map(|area| area.music.as_str());
+
+  let mut playing = false;
+  for mut track in tracks.iter_mut() {
+    let target = if Some(track.path.as_str()) == music {
+      playing = true;
+      1.0
+    } else {
+      0.0
+    };
+    if track.target != target {
+      track.target = target;
+    }
+  }
+  let (Some(music), false) = (music, playing) else {
+    return;
+  };
+  // areas added since loading, say by the editor, load on the spot
+  let source = area_music
+    .0
+    .get(music)
+    .cloned()
+    .unwrap_or_else(|| asset_server.load(music.to_string()));
+  if !failed(&asset_server, &source) {
+    commands.spawn((
+      AudioBundle {
+        source,
+        settings: PlaybackSettings::LOOP.with_volume(Volume::new_absolute(0.0)),
+      },
+      MusicTrack {
+        path:   music.to_string(),
+        fade:   0.0,
+        target: 1.0,
+      },
+      Name::new("music"),
+      RunEntity,
+    ));
+  }
+}
+
+fn crossfade_music(
+  mut commands: Commands,
+  mut tracks: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+  settings: Res<AudioSettings>,
+  time: Res<Time<Real>>,
+) {
+  let step = time.delta_seconds() / CROSSFADE_SECONDS;
+  for (entity, mut track, sink) in tracks.iter_mut() {
+    let fade = if track.fade < track.target {
+      (track.fade + step).min(track.target)
+    } else {
+      (track.fade - step).max(track.target)
+    };
+    if fade != track.fade {
+      track.fade = fade;
+    }
+    if track.fade <= 0.0 && track.target <= 0.0 {
+      commands.entity(entity).despawn_recursive();
+      continue;
+    }
+    if let Some(sink) = sink {
+      sink.set_volume(track.fade * settings.music_volume());
+    }
+  }
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<AudioSettings>()
+      .insert_resource(AudioSettings::load())
+      .init_resource::<FootstepSounds>()
+      .init_resource::<AreaMusic>()
+      .add_systems(OnEnter(AppState::Loading), track_footstep_sounds)
+      .add_systems(
+        Update,
+        track_area_music
+          .before(check_loading)
+          .run_if(in_state(AppState::Loading)),
+      )
+      .add_systems(
+        Update,
+        ((volume_keys, save_audio_settings).chain(), crossfade_music),
+      )
+      .add_systems(
+        Update,
+        (
+          play_footsteps.after(crate::player::update_player_sprite),
+          choose_area_music.before(crossfade_music),
+        )
+          .in_set(GameplaySet),
+      );
+  }
+}
@@ -1,12 +1,19 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::camera::Viewport, window::PrimaryWindow};
 
-use super::player::Player;
+use super::player::{Player, PlayerId};
 
 pub struct CameraPlugin;
 
+/// The world units covered by one logical pixel at the default zoom.
+const BASE_SCALE: f32 = 1.0 / 64.0;
+
+/// A gameplay camera. A camera with a `player` follows that player through
+/// its own viewport; a camera without one frames every player at once.
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
-pub struct MainCamera;
+pub struct MainCamera {
+  pub player: Option<Entity>,
+}
 
 #[derive(Resource, Reflect)]
 #[reflect(Resource)]
@@ -16,39 +23,194 @@ impl Default for CameraPlayerOffset {
   fn default() -> Self { CameraPlayerOffset(Vec2::new(0.0, 0.25)) }
 }
 
+/// How the window is shared between local players.
+#[derive(Resource, Reflect, Clone, Copy, PartialEq, Eq, Default)]
+#[reflect(Resource)]
+pub enum CameraMode {
+  /// One viewport per player, tiled across the window.
+  #[default]
+  Split,
+  /// A single viewport that zooms out to keep every player in frame.
+  Shared,
+}
+
+/// The world units of padding kept around the players in shared mode.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SharedCameraMargin(pub Vec2);
+
+impl Default for SharedCameraMargin {
+  fn default() -> Self { SharedCameraMargin(Vec2::new(6.0, 6.0)) }
+}
+
 impl Plugin for CameraPlugin {
   fn build(&self, app: &mut App) {
     app
       .register_type::<MainCamera>()
       .register_type::<CameraPlayerOffset>()
+      .register_type::<CameraMode>()
+      .register_type::<SharedCameraMargin>()
       .init_resource::<CameraPlayerOffset>()
-      .add_systems(Startup, setup)
-      .add_systems(Update, follow_player.after(crate::player::apply_movement));
+      .init_resource::<CameraMode>()
+      .init_resource::<SharedCameraMargin>()
+      .add_systems(
+        Update,
+        (
+          toggle_camera_mode,
+          sync_cameras,
+          apply_deferred,
+          update_viewports,
+          follow_player.after(crate::player::apply_movement),
+        )
+          .chain(),
+      );
   }
 }
 
-fn setup(mut commands: Commands) {
+fn spawn_camera(commands: &mut Commands, player: Option<Entity>) {
   commands.spawn((
     Camera2dBundle {
       projection: OrthographicProjection {
         near: -1000.0,
-        scale: 64.0_f32.recip(),
+        scale: BASE_SCALE,
         ..default()
       },
       ..default()
     },
-    MainCamera,
+    Name::new("camera"),
+    MainCamera { player },
   ));
 }
 
+fn toggle_camera_mode(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut mode: ResMut<CameraMode>,
+) {
+  if keyboard_input.just_pressed(KeyCode::F2) {
+    *mode = match *mode {
+      CameraMode::Split => CameraMode::Shared,
+      CameraMode::Shared => CameraMode::Split,
+    };
+  }
+}
+
+/// Spawns and despawns cameras so there is one per player in split mode, or
+/// exactly one in shared mode.
+fn sync_cameras(
+  mut commands: Commands,
+  mode: Res<CameraMode>,
+  players: Query<Entity, With<Player>>,
+  cameras: Query<(Entity, &MainCamera)>,
+) {
+  let wanted: Vec<Option<Entity>> = match *mode {
+    CameraMode::Split if !players.is_empty() => {
+      players.iter().map(Some).collect()
+    }
+    _ => vec![None],
+  };
+
+  for (entity, camera) in cameras.iter() {
+    if !wanted.contains(&camera.player) {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+  for player in wanted {
+    if !cameras.iter().any(|(_, camera)| camera.player == player) {
+      spawn_camera(&mut commands, player);
+    }
+  }
+}
+
+/// Tiles the split-screen viewports across the window in player order.
+fn update_viewports(
+  windows: Query<&Window, With<PrimaryWindow>>,
+  mut cameras: Query<(&MainCamera, &mut Camera)>,
+  players: Query<&PlayerId>,
+) {
+  let Ok(window) = windows.get_single() else {
+    return;
+  };
+  let window_size =
+    UVec2::new(window.physical_width(), window.physical_height());
+
+  let mut cameras = cameras.iter_mut().collect::<Vec<_>>();
+  cameras.sort_by_key(|(main_camera, _)| {
+    main_camera
+      .player
+      .and_then(|player| players.get(player).ok().copied())
+  });
+
+  let count = cameras.len() as u32;
+  let columns = (count as f32).sqrt().ceil().max(1.0) as u32;
+  let rows = count.div_ceil(columns).max(1);
+  let cell_size = window_size / UVec2::new(columns, rows);
+
+  for (index, (main_camera, camera)) in cameras.iter_mut().enumerate() {
+    let index = index as u32;
+    if camera.order != index as isize {
+      camera.order = index as isize;
+    }
+    let viewport = main_camera.player.map(|_| Viewport {
+      physical_position: UVec2::new(index % columns, index / columns)
+        * cell_size,
+      physical_size: cell_size.max(UVec2::ONE),
+      ..default()
+    });
+    let unchanged = match (&camera.viewport, &viewport) {
+      (Some(old), Some(new)) => {
+        old.physical_position == new.physical_position
+          && old.physical_size == new.physical_size
+      }
+      (None, None) => true,
+      _ => false,
+    };
+    if !unchanged {
+      camera.viewport = viewport;
+    }
+  }
+}
+
 fn follow_player(
-  mut query: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
-  player: Query<&Transform, With<Player>>,
+  mut cameras: Query<
+    (
+      &MainCamera,
+      &Camera,
+      &mut Transform,
+      &mut OrthographicProjection,
+    ),
+    Without<Player>,
+  >,
+  players: Query<&Transform, With<Player>>,
   offset: Res<CameraPlayerOffset>,
+  margin: Res<SharedCameraMargin>,
 ) {
-  if let Some(mut transform) = query.iter_mut().next() {
-    let player_transform = player.single();
-    transform.translation =
-      (player_transform.translation.xy() - offset.0).extend(0.0);
+  for (main_camera, camera, mut transform, mut projection) in cameras.iter_mut()
+  {
+    match main_camera.player {
+      Some(player) => {
+        let Ok(player_transform) = players.get(player) else {
+          continue;
+        };
+        transform.translation =
+          (player_transform.translation.xy() - offset.0).extend(0.0);
+        projection.scale = BASE_SCALE;
+      }
+      None => {
+        let mut positions = players.iter().map(|t| t.translation.xy());
+        let Some(first) = positions.next() else {
+          continue;
+        };
+        let (min, max) = positions
+          .fold((first, first), |(min, max), p| (min.min(p), max.max(p)));
+        transform.translation = ((min + max) / 2.0 - offset.0).extend(0.0);
+
+        // zoom out just enough to fit every player plus the margin
+        let Some(view_size) = camera.logical_viewport_size() else {
+          continue;
+        };
+        let needed = (max - min + margin.0) / view_size;
+        projection.scale = needed.max_element().max(BASE_SCALE);
+      }
+    }
   }
 }
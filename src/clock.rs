@@ -0,0 +1,143 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+  lighting::AmbientLight2d,
+  state::{EndRun, GameplaySet},
+};
+
+/// How far through the day the sun rises.
+const DAWN: f32 = 0.25;
+/// How far through the day the sun sets.
+const DUSK: f32 = 0.75;
+/// The ambient brightness at midnight.
+const NIGHT_BRIGHTNESS: f32 = 0.15;
+const NIGHT_TINT: Color = Color::rgb(0.02, 0.03, 0.12);
+const TWILIGHT_TINT: Color = Color::rgb(0.25, 0.1, 0.03);
+
+/// The time of day in the world. Runs on virtual time, so it stops while the
+/// game is paused.
+#[derive(Resource, Clone, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct WorldClock {
+  /// Real seconds in a day.
+  pub day_length: f32,
+  /// Days since the run started.
+  pub day:        u32,
+  /// How far through the day it is, from 0.0 at midnight to 1.0.
+  pub time:       f32,
+}
+
+impl Default for WorldClock {
+  fn default() -> Self {
+    WorldClock {
+      day_length: 480.0,
+      day:        0,
+      time:       0.3,
+    }
+  }
+}
+
+impl WorldClock {
+  pub fn is_night(&self) -> bool { !(DAWN..DUSK).contains(&self.time) }
+
+  /// The height of the sun, from 0.0 at midnight to 1.0 at noon.
+  fn sun(&self) -> f32 { 0.5 - (self.time * TAU).cos() * 0.5 }
+
+  /// The ambient light at this time of day.
+  pub fn ambient(&self) -> AmbientLight2d {
+    let sun = self.sun();
+    let day = ((sun - 0.3) / 0.5).clamp(0.0, 1.0);
+    // strongest when the sun is on the horizon
+    let twilight = (1.0 - (sun - 0.5).abs() * 4.0).clamp(0.0, 1.0);
+    let [r, g, b, _] = NIGHT_TINT.as_rgba_f32();
+    let [tr, tg, tb, _] = TWILIGHT_TINT.as_rgba_f32();
+    AmbientLight2d {
+      brightness: NIGHT_BRIGHTNESS + (1.0 - NIGHT_BRIGHTNESS) * day,
+      tint:       Color::rgb(
+        r + (tr - r) * twilight,
+        g + (tg - g) * twilight,
+        b + (tb - b) * twilight,
+      ),
+    }
+  }
+}
+
+/// Sent when the sun rises or sets, for anything that keeps a schedule.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DayEvent {
+  Dawn { day: u32 },
+  Dusk { day: u32 },
+}
+
+fn advance_clock(
+  mut clock: ResMut<WorldClock>,
+  mut events: EventWriter<DayEvent>,
+  time: Res<Time>,
+) {
+  if clock.day_length <= 0.0 {
+    return;
+  }
+  let before = clock.time;
+  // never skip more than a day, even after a long frame
+  let after = before + (time.delta_seconds() / clock.day_length).min(0.99);
+  let crossed = |at: f32| before < at && at <= after;
+  for (offset, day) in [(0.0, clock.day), (1.0, clock.day + 1)] {
+    if crossed(DAWN + offset) {
+      events.send(DayEvent::Dawn { day });
+    }
+    if crossed(DUSK + offset) {
+      events.send(DayEvent::Dusk { day });
+    }
+  }
+  if after >= 1.0 {
+    clock.day += 1;
+    clock.time = after - 1.0;
+  } else {
+    clock.time = after;
+  }
+}
+
+/// Fades the ambient light with the time of day, in steps small enough not to
+/// be noticed so the light map isn't redrawn every frame.
+fn update_ambient_light(
+  clock: Res<WorldClock>,
+  mut ambient: ResMut<AmbientLight2d>,
+) {
+  if !clock.is_changed() {
+    return;
+  }
+  let quantize = |v: f32| (v * 128.0).round() / 128.0;
+  let target = clock.ambient();
+  let [r, g, b, _] = target.tint.as_rgba_f32();
+  ambient.set_if_neq(AmbientLight2d {
+    brightness: quantize(target.brightness),
+    tint:       Color::rgb(quantize(r), quantize(g), quantize(b)),
+  });
+}
+
+fn reset_clock(mut clock: ResMut<WorldClock>) {
+  *clock = WorldClock {
+    day_length: clock.day_length,
+    ..default()
+  };
+}
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<WorldClock>()
+      .init_resource::<WorldClock>()
+      .add_event::<DayEvent>()
+      .add_systems(
+        Update,
+        (advance_clock, update_ambient_light)
+          .chain()
+          .in_set(GameplaySet),
+      )
+      .add_systems(EndRun, reset_clock);
+  }
+}
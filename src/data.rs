@@ -0,0 +1,54 @@
+use std::marker::PhantomData;
+
+use bevy::{
+  asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+  prelude::*,
+  utils::BoxedFuture,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Loads any deserializable asset from a RON file with one of the given
+/// extensions.
+pub struct RonAssetLoader<A> {
+  extensions: &'static [&'static str],
+  _asset:     PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+  pub fn new(extensions: &'static [&'static str]) -> Self {
+    Self {
+      extensions,
+      _asset: PhantomData,
+    }
+  }
+}
+
+#[derive(Debug, Error)]
+pub enum RonLoaderError {
+  #[error("could not read asset: {0}")]
+  Io(#[from] std::io::Error),
+  #[error("could not parse asset: {0}")]
+  Ron(#[from] ron::error::SpannedError),
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+  type Asset = A;
+  type Settings = ();
+  type Error = RonLoaderError;
+
+  fn load<'a>(
+    &'a self,
+    reader: &'a mut Reader,
+    _settings: &'a Self::Settings,
+    _load_context: &'a mut LoadContext,
+  ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+    Box::pin(async move {
+      let mut bytes = Vec::new();
+      reader.read_to_end(&mut bytes).await?;
+      Ok(ron::de::from_bytes(&bytes)?)
+    })
+  }
+
+  fn extensions(&self) -> &[&str] { self.extensions }
+}
@@ -0,0 +1,362 @@
+mod script;
+
+use bevy::prelude::*;
+
+pub use self::script::*;
+use crate::{
+  data::RonAssetLoader,
+  flags::GameFlags,
+  interaction::{InteractionEvent, InteractionKind},
+  player::{
+    input::{InputSources, PlayerInput},
+    status::PlayerStatus,
+    Player,
+  },
+  state::{EndRun, GameplaySet},
+  tile::Direction8,
+};
+
+/// Lets players talk to this entity.
+#[derive(Component, Clone)]
+pub struct Dialogue(pub Handle<DialogueScript>);
+
+/// Asks for a conversation to open. Ignored while another one is open.
+#[derive(Event, Clone)]
+pub struct StartDialogue {
+  pub player:  Entity,
+  pub speaker: Option<Entity>,
+  pub script:  Handle<DialogueScript>,
+}
+
+/// Sent when a player picks a choice.
+#[derive(Event, Clone, Debug)]
+pub struct DialogueChoiceEvent {
+  pub player:  Entity,
+  pub speaker: Option<Entity>,
+  pub node:    String,
+  pub choice:  usize,
+  pub id:      Option<String>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DialogueEnded {
+  pub player:  Entity,
+  pub speaker: Option<Entity>,
+}
+
+/// The conversation currently on screen. Only one can be open at a time, and
+/// every player's movement is held while it is.
+#[derive(Resource, Clone)]
+pub struct ActiveDialogue {
+  pub script:   Handle<DialogueScript>,
+  pub player:   Entity,
+  pub speaker:  Option<Entity>,
+  pub node:     String,
+  pub line:     usize,
+  /// The highlighted entry among the available choices.
+  pub selected: usize,
+}
+
+pub fn dialogue_open(dialogue: Option<Res<ActiveDialogue>>) -> bool {
+  dialogue.is_some()
+}
+
+#[derive(Component)]
+struct DialoguePanel;
+
+fn talk_to_speakers(
+  mut interactions: EventReader<InteractionEvent>,
+  speakers: Query<&Dialogue>,
+  mut starts: EventWriter<StartDialogue>,
+) {
+  for event in interactions.read() {
+    if event.kind != InteractionKind::Talk {
+      continue;
+    }
+    let Ok(dialogue) = speakers.get(event.target) else {
+      continue;
+    };
+    starts.send(StartDialogue {
+      player:  event.player,
+      speaker: Some(event.target),
+      script:  dialogue.0.clone(),
+    });
+  }
+}
+
+fn start_dialogue(
+  mut commands: Commands,
+  mut starts: EventReader<StartDialogue>,
+  active: Option<Res<ActiveDialogue>>,
+  scripts: Res<Assets<DialogueScript>>,
+  mut flags: ResMut<GameFlags>,
+) {
+  let mut open = active.is_some();
+  for start in starts.read() {
+    if open {
+      continue;
+    }
+    let Some(script) = scripts.get(&start.script) else {
+      warn!("tried to start a dialogue that hasn't loaded");
+      continue;
+    };
+    let Some(node) = script.nodes.get(&script.start) else {
+      error!("dialogue starts on missing node {:?}", script.start);
+      continue;
+    };
+    node.effects.iter().for_each(|e| e.apply(&mut flags));
+    commands.insert_resource(ActiveDialogue {
+      script:   start.script.clone(),
+      player:   start.player,
+      speaker:  start.speaker,
+      node:     script.start.clone(),
+      line:     0,
+      selected: 0,
+    });
+    open = true;
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advance_dialogue(
+  mut commands: Commands,
+  active: Option<ResMut<ActiveDialogue>>,
+  inputs: InputSources,
+  players: Query<&PlayerInput>,
+  scripts: Res<Assets<DialogueScript>>,
+  mut flags: ResMut<GameFlags>,
+  mut choices: EventWriter<DialogueChoiceEvent>,
+  mut ended: EventWriter<DialogueEnded>,
+) {
+  let Some(mut active) = active else {
+    return;
+  };
+  let script = scripts.get(&active.script);
+  let node = script.and_then(|s| s.nodes.get(&active.node));
+  let (Some(node), Ok(input)) = (node, players.get(active.player)) else {
+    // the script was unloaded or the player left; nothing left to show
+    commands.remove_resource::<ActiveDialogue>();
+    return;
+  };
+
+  let on_last_line = active.line + 1 >= node.lines.len();
+  let available = node.available_choices(&flags);
+
+  let step = inputs.menu_step(input);
+  if on_last_line && step != 0 && !available.is_empty() {
+    let count = available.len() as i32;
+    active.selected =
+      (active.selected as i32 + step).rem_euclid(count) as usize;
+  }
+
+  if !inputs.interact(input) {
+    return;
+  }
+  if !on_last_line {
+    active.line += 1;
+    return;
+  }
+
+  let next = if let Some(&index) = available.get(active.selected) {
+    let choice = &node.choices[index];
+    choice.effects.iter().for_each(|e| e.apply(&mut flags));
+    choices.send(DialogueChoiceEvent {
+      player:  active.player,
+      speaker: active.speaker,
+      node:    active.node.clone(),
+      choice:  index,
+      id:      choice.id.clone(),
+    });
+    choice.next.as_deref()
+  } else {
+    node.next_node(&flags)
+  };
+
+  let next_node = next.and_then(|name| {
+    let found = script.and_then(|s| s.nodes.get(name));
+    if found.is_none() {
+      error!("dialogue goes to missing node {name:?}");
+    }
+    found.map(|node| (name, node))
+  });
+  match next_node {
+    Some((name, node)) => {
+      node.effects.iter().for_each(|e| e.apply(&mut flags));
+      active.node = name.to_string();
+      active.line = 0;
+      active.selected = 0;
+    }
+    None => {
+      commands.remove_resource::<ActiveDialogue>();
+      ended.send(DialogueEnded {
+        player:  active.player,
+        speaker: active.speaker,
+      });
+    }
+  }
+}
+
+/// Holds the talking player still, turned towards whoever they're talking to.
+fn face_speaker(
+  active: Res<ActiveDialogue>,
+  mut players: Query<(&mut Player, &Transform)>,
+  speakers: Query<&GlobalTransform>,
+) {
+  let speaker = active.speaker.and_then(|s| speakers.get(s).ok());
+  let Ok((mut player, transform)) = players.get_mut(active.player) else {
+    return;
+  };
+  let facing = speaker
+    .and_then(|speaker| {
+      Direction8::try_from(
+        speaker.translation().xy() - transform.translation.xy(),
+      )
+      .ok()
+    })
+    .unwrap_or(player.0.facing());
+  let status = PlayerStatus::Stand(facing);
+  if player.0 != status {
+    player.0 = status;
+  }
+}
+
+/// Rebuilds the dialogue panel whenever the conversation moves on.
+fn render_dialogue(
+  mut commands: Commands,
+  active: Option<Res<ActiveDialogue>>,
+  panels: Query<Entity, With<DialoguePanel>>,
+  scripts: Res<Assets<DialogueScript>>,
+  flags: Res<GameFlags>,
+  asset_server: Res<AssetServer>,
+) {
+  // `is_none_or` is newer than the pinned toolchain
+  #[allow(unknown_lints, clippy::unnecessary_map_or)]
+  let changed = active.as_ref().map_or(true, |a| a.is_changed());
+  if !changed {
+    return;
+  }
+  for panel in panels.iter() {
+    commands.entity(panel).despawn_recursive();
+  }
+
+  let Some(active) = active else {
+    return;
+  };
+  let Some(script) = scripts.get(&active.script) else {
+    return;
+  };
+  let Some(node) = script.nodes.get(&active.node) else {
+    return;
+  };
+  let speaker = node.speaker.as_ref().and_then(|s| script.speakers.get(s));
+
+  let text_style = TextStyle {
+    font_size: 20.0,
+    color: Color::WHITE,
+    ..default()
+  };
+  let line = node.lines.get(active.line).cloned().unwrap_or_default();
+  let on_last_line = active.line + 1 >= node.lines.len();
+
+  commands
+    .spawn((
+      NodeBundle {
+        style: Style {
+          position_type: PositionType::Absolute,
+          bottom: Val::Px(16.0),
+          left: Val::Percent(10.0),
+          width: Val::Percent(80.0),
+          padding: UiRect::all(Val::Px(12.0)),
+          column_gap: Val::Px(12.0),
+          ..default()
+        },
+        background_color: Color::rgba(0.05, 0.05, 0.1, 0.9).into(),
+        ..default()
+      },
+      Name::new("dialogue panel"),
+      DialoguePanel,
+    ))
+    .with_children(|panel| {
+      if let Some(portrait) = speaker.and_then(|s| s.portrait.clone()) {
+        panel.spawn(ImageBundle {
+          style: Style {
+            width: Val::Px(64.0),
+            height: Val::Px(64.0),
+            ..default()
+          },
+          image: asset_server.load(portrait).into(),
+          ..default()
+        });
+      }
+      panel
+        .spawn(NodeBundle {
+          style: Style {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+          },
+          ..default()
+        })
+        .with_children(|column| {
+          if let Some(speaker) = speaker {
+            column.spawn(TextBundle::from_section(
+              speaker.name.clone(),
+              TextStyle {
+                color: Color::GOLD,
+                ..text_style.clone()
+              },
+            ));
+          }
+          column.spawn(TextBundle::from_section(line, text_style.clone()));
+          if !on_last_line {
+            return;
+          }
+          let available = node.available_choices(&flags);
+          for (position, &index) in available.iter().enumerate() {
+            let marker = if position == active.selected {
+              "> "
+            } else {
+              "  "
+            };
+            column.spawn(TextBundle::from_section(
+              format!("{marker}{}", node.choices[index].text),
+              text_style.clone(),
+            ));
+          }
+        });
+    });
+}
+
+fn close_dialogue(mut commands: Commands) {
+  commands.remove_resource::<ActiveDialogue>();
+}
+
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_asset::<DialogueScript>()
+      .register_asset_loader(RonAssetLoader::<DialogueScript>::new(&[
+        "dialogue.ron",
+      ]))
+      .add_event::<StartDialogue>()
+      .add_event::<DialogueChoiceEvent>()
+      .add_event::<DialogueEnded>()
+      .add_systems(
+        Update,
+        (
+          (talk_to_speakers, start_dialogue, advance_dialogue)
+            .chain()
+            .after(crate::interaction::interact)
+            .in_set(GameplaySet),
+          face_speaker
+            .run_if(dialogue_open)
+            .before(crate::player::apply_movement)
+            .in_set(GameplaySet),
+          render_dialogue.after(advance_dialogue),
+        ),
+      )
+      .add_systems(EndRun, close_dialogue);
+  }
+}
@@ -0,0 +1,89 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::flags::{Condition, FlagEffect, GameFlags};
+
+/// A conversation, loaded from a `.dialogue.ron` file.
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct DialogueScript {
+  /// The node the conversation opens on.
+  pub start:    String,
+  #[serde(default)]
+  pub speakers: HashMap<String, Speaker>,
+  pub nodes:    HashMap<String, DialogueNode>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Speaker {
+  pub name:     String,
+  /// The asset path of the portrait shown next to this speaker's lines.
+  #[serde(default)]
+  pub portrait: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DialogueNode {
+  /// A key into the script's speakers; `None` for narration.
+  #[serde(default)]
+  pub speaker: Option<String>,
+  pub lines:   Vec<String>,
+  /// Offered after the last line. Choices whose condition fails are hidden.
+  #[serde(default)]
+  pub choices: Vec<Choice>,
+  /// Where to go after the last line when there are no choices. The first
+  /// branch whose condition holds is taken; if none do, the dialogue ends.
+  #[serde(default)]
+  pub goto:    Vec<Branch>,
+  /// Applied as soon as the node is entered.
+  #[serde(default)]
+  pub effects: Vec<FlagEffect>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Choice {
+  pub text:      String,
+  /// Reported in `DialogueChoiceEvent`, so gameplay can react to the outcome.
+  #[serde(default)]
+  pub id:        Option<String>,
+  #[serde(default)]
+  pub condition: Option<Condition>,
+  #[serde(default)]
+  pub effects:   Vec<FlagEffect>,
+  /// The node to go to next; `None` ends the dialogue.
+  #[serde(default)]
+  pub next:      Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Branch {
+  #[serde(default)]
+  pub condition: Option<Condition>,
+  pub node:      String,
+}
+
+// `is_none_or` is newer than the pinned toolchain
+#[allow(unknown_lints, clippy::unnecessary_map_or)]
+fn holds(condition: &Option<Condition>, flags: &GameFlags) -> bool {
+  condition.as_ref().map_or(true, |c| c.evaluate(flags))
+}
+
+impl DialogueNode {
+  /// The indices of the choices currently on offer.
+  pub fn available_choices(&self, flags: &GameFlags) -> Vec<usize> {
+    self
+      .choices
+      .iter()
+      .enumerate()
+      .filter(|(_, choice)| holds(&choice.condition, flags))
+      .map(|(index, _)| index)
+      .collect()
+  }
+  /// The node to continue to after the last line, if any.
+  pub fn next_node(&self, flags: &GameFlags) -> Option<&str> {
+    self
+      .goto
+      .iter()
+      .find(|branch| holds(&branch.condition, flags))
+      .map(|branch| branch.node.as_str())
+  }
+}
@@ -0,0 +1,383 @@
+mod ui;
+
+use std::{collections::VecDeque, fs};
+
+use bevy::{
+  prelude::*,
+  utils::{HashMap, HashSet},
+  window::PrimaryWindow,
+};
+use ron::ser::PrettyConfig;
+
+use crate::{
+  camera::MainCamera,
+  map::{MapCell, MapData, MapHandle, MapTile},
+  state::GameplaySet,
+  tile::TilePosition,
+};
+
+/// How far a flood fill may spread past the edges of the painted map.
+const FILL_MARGIN: i64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EditorTool {
+  #[default]
+  Brush,
+  Fill,
+  Rectangle,
+  Eraser,
+}
+
+impl EditorTool {
+  pub const ALL: [EditorTool; 4] = [
+    EditorTool::Brush,
+    EditorTool::Fill,
+    EditorTool::Rectangle,
+    EditorTool::Eraser,
+  ];
+  pub fn label(&self) -> &'static str {
+    match self {
+      EditorTool::Brush => "Brush",
+      EditorTool::Fill => "Fill",
+      EditorTool::Rectangle => "Rectangle",
+      EditorTool::Eraser => "Eraser",
+    }
+  }
+}
+
+/// One cell's contents before and after an edit.
+#[derive(Clone)]
+struct CellChange {
+  pos:    TilePosition,
+  before: Option<MapCell>,
+  after:  Option<MapCell>,
+}
+
+/// The map editor, toggled with F3. Edits go straight into the loaded map
+/// asset, and the map's hot reloading brings the tiles in the world up to
+/// date.
+#[derive(Resource, Default)]
+pub struct MapEditor {
+  pub open:            bool,
+  pub tool:            EditorTool,
+  pub brush:           MapTile,
+  pub variant:         usize,
+  pub layer:           u8,
+  /// Set by the editor panel while the pointer is over it, so clicks on the
+  /// panel don't paint the map underneath.
+  pub pointer_over_ui: bool,
+  /// Whether there are edits that haven't been saved to the map file.
+  pub dirty:           bool,
+  drag_start:          Option<TilePosition>,
+  /// The changes made by the stroke in progress, undone as one step.
+  stroke:              Vec<CellChange>,
+  undo:                Vec<Vec<CellChange>>,
+  redo:                Vec<Vec<CellChange>>,
+}
+
+impl MapEditor {
+  pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+  pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+  fn brush_cell(&self, pos: TilePosition) -> MapCell {
+    MapCell {
+      x:       pos.x,
+      y:       pos.y,
+      layer:   pos.layer,
+      tile:    self.brush.clone(),
+      variant: self.variant,
+    }
+  }
+
+  /// Sets one cell, recording the change in the current stroke.
+  fn paint(
+    &mut self,
+    map: &mut Assets<MapData>,
+    handle: &Handle<MapData>,
+    pos: TilePosition,
+    cell: Option<MapCell>,
+  ) {
+    let Some(current) = map.get(handle) else {
+      return;
+    };
+    let before = current.get(pos).cloned();
+    if before == cell {
+      return;
+    }
+    // only borrow mutably on a real change, since it counts as a reload
+    let Some(map) = map.get_mut(handle) else {
+      return;
+    };
+    map.set(pos, cell.clone());
+    self.stroke.push(CellChange {
+      pos,
+      before,
+      after: cell,
+    });
+    self.dirty = true;
+  }
+
+  fn finish_stroke(&mut self) {
+    if self.stroke.is_empty() {
+      return;
+    }
+    self.undo.push(std::mem::take(&mut self.stroke));
+    self.redo.clear();
+  }
+
+  pub fn undo(&mut self, maps: &mut Assets<MapData>, handle: &Handle<MapData>) {
+    self.finish_stroke();
+    let (Some(changes), Some(map)) = (self.undo.pop(), maps.get_mut(handle))
+    else {
+      return;
+    };
+    for change in changes.iter().rev() {
+      map.set(change.pos, change.before.clone());
+    }
+    self.redo.push(changes);
+    self.dirty = true;
+  }
+
+  pub fn redo(&mut self, maps: &mut Assets<MapData>, handle: &Handle<MapData>) {
+    let (Some(changes), Some(map)) = (self.redo.pop(), maps.get_mut(handle))
+    else {
+      return;
+    };
+    for change in &changes {
+      map.set(change.pos, change.after.clone());
+    }
+    self.undo.push(changes);
+    self.dirty = true;
+  }
+
+  /// Writes the map back over the file it was loaded from.
+  pub fn save(
+    &mut self,
+    maps: &Assets<MapData>,
+    handle: &Handle<MapData>,
+    asset_server: &AssetServer,
+  ) {
+    let (Some(map), Some(path)) =
+      (maps.get(handle), asset_server.get_path(handle.id()))
+    else {
+      return;
+    };
+    let path = format!("assets/{}", path.path().display());
+    // one cell per line, like the hand-written files
+    let config = PrettyConfig::default().depth_limit(2);
+    let result = ron::ser::to_string_pretty(map, config)
+      .map_err(|e| e.to_string())
+      .and_then(|text| fs::write(&path, text).map_err(|e| e.to_string()));
+    match result {
+      Ok(()) => {
+        info!("saved map to {path}");
+        self.dirty = false;
+      }
+      Err(e) => error!("failed to save map: {e}"),
+    }
+  }
+}
+
+pub fn editor_open(editor: Res<MapEditor>) -> bool { editor.open }
+
+/// Snaps a world position to a cell. Ground tiles sit on even coordinates and
+/// everything above them on odd ones, as in the map files.
+fn snap(world: Vec2, layer: u8) -> TilePosition {
+  let offset = if layer == 0 { 0.0 } else { 1.0 };
+  let snap = |v: f32| (((v - offset) / 2.0).round() * 2.0 + offset) as i64;
+  TilePosition::new(snap(world.x), snap(world.y), layer)
+}
+
+/// The cells of the rectangle with the given corners.
+fn rectangle(a: TilePosition, b: TilePosition) -> Vec<TilePosition> {
+  let xs = (a.x.min(b.x)..=a.x.max(b.x)).step_by(2);
+  xs.flat_map(|x| {
+    (a.y.min(b.y)..=a.y.max(b.y))
+      .step_by(2)
+      .map(move |y| TilePosition::new(x, y, a.layer))
+  })
+  .collect()
+}
+
+/// The cells connected to `start` holding the same tile, on the same layer.
+/// Empty regions stop a little past the edge of the map.
+fn flood(map: &MapData, start: TilePosition) -> Vec<TilePosition> {
+  let cells = map
+    .tiles
+    .iter()
+    .enumerate()
+    .map(|(index, cell)| (cell.position(), index))
+    .collect::<HashMap<_, _>>();
+  let tile_at =
+    |pos: TilePosition| cells.get(&pos).map(|&i| &map.tiles[i].tile);
+  let target = tile_at(start);
+  let bounds = map.tiles.iter().fold(
+    (start.x, start.y, start.x, start.y),
+    |(min_x, min_y, max_x, max_y), cell| {
+      (
+        min_x.min(cell.x),
+        min_y.min(cell.y),
+        max_x.max(cell.x),
+        max_y.max(cell.y),
+      )
+    },
+  );
+  let in_bounds = |pos: TilePosition| {
+    (bounds.0 - FILL_MARGIN..=bounds.2 + FILL_MARGIN).contains(&pos.x)
+      && (bounds.1 - FILL_MARGIN..=bounds.3 + FILL_MARGIN).contains(&pos.y)
+  };
+
+  let mut found = HashSet::default();
+  found.insert(start);
+  let mut queue = VecDeque::from([start]);
+  while let Some(pos) = queue.pop_front() {
+    for (dx, dy) in [(2, 0), (-2, 0), (0, 2), (0, -2)] {
+      let next = TilePosition::new(pos.x + dx, pos.y + dy, pos.layer);
+      if !in_bounds(next) || found.contains(&next) || tile_at(next) != target {
+        continue;
+      }
+      found.insert(next);
+      queue.push_back(next);
+    }
+  }
+  found.into_iter().collect()
+}
+
+fn toggle_editor(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut editor: ResMut<MapEditor>,
+) {
+  if keyboard_input.just_pressed(KeyCode::F3) {
+    editor.open = !editor.open;
+    editor.drag_start = None;
+    editor.finish_stroke();
+  }
+}
+
+fn editor_shortcuts(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut editor: ResMut<MapEditor>,
+  mut maps: ResMut<Assets<MapData>>,
+  handle: Res<MapHandle>,
+  asset_server: Res<AssetServer>,
+) {
+  if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+  {
+    return;
+  }
+  let shift =
+    keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+  if keyboard_input.just_pressed(KeyCode::Z) && !shift {
+    editor.undo(&mut maps, &handle.0);
+  } else if keyboard_input.just_pressed(KeyCode::Y)
+    || (keyboard_input.just_pressed(KeyCode::Z) && shift)
+  {
+    editor.redo(&mut maps, &handle.0);
+  }
+  if keyboard_input.just_pressed(KeyCode::S) {
+    editor.save(&maps, &handle.0, &asset_server);
+  }
+}
+
+/// The world position under the cursor, through whichever camera's viewport
+/// it is in.
+fn cursor_world_position(
+  window: &Window,
+  cameras: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+  let cursor = window.cursor_position()?;
+  cameras.iter().find_map(|(camera, transform)| {
+    let rect = camera.logical_viewport_rect()?;
+    if !rect.contains(cursor) {
+      return None;
+    }
+    camera.viewport_to_world_2d(transform, cursor - rect.min)
+  })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn paint_map(
+  mouse: Res<Input<MouseButton>>,
+  windows: Query<&Window, With<PrimaryWindow>>,
+  cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+  mut editor: ResMut<MapEditor>,
+  mut maps: ResMut<Assets<MapData>>,
+  handle: Res<MapHandle>,
+  mut gizmos: Gizmos,
+) {
+  let Ok(window) = windows.get_single() else {
+    return;
+  };
+  if mouse.just_released(MouseButton::Left) {
+    if let Some(start) = editor.drag_start.take() {
+      if let Some(world) = cursor_world_position(window, &cameras) {
+        let end = snap(world, editor.layer);
+        for pos in rectangle(start, end) {
+          let cell = editor.brush_cell(pos);
+          editor.paint(&mut maps, &handle.0, pos, Some(cell));
+        }
+      }
+    }
+    editor.finish_stroke();
+  }
+
+  let Some(world) = cursor_world_position(window, &cameras) else {
+    return;
+  };
+  let pos = snap(world, editor.layer);
+  let cell_center = Vec2::new(pos.x as f32, pos.y as f32);
+  gizmos.rect_2d(cell_center, 0.0, Vec2::splat(2.0), Color::YELLOW);
+  if let Some(start) = editor.drag_start {
+    let corner = Vec2::new(start.x as f32, start.y as f32);
+    gizmos.rect_2d(
+      (corner + cell_center) / 2.0,
+      0.0,
+      (corner - cell_center).abs() + Vec2::splat(2.0),
+      Color::ORANGE,
+    );
+  }
+
+  if editor.pointer_over_ui {
+    return;
+  }
+  let pressed = mouse.pressed(MouseButton::Left);
+  let just_pressed = mouse.just_pressed(MouseButton::Left);
+  match editor.tool {
+    EditorTool::Brush if pressed => {
+      let cell = editor.brush_cell(pos);
+      editor.paint(&mut maps, &handle.0, pos, Some(cell));
+    }
+    EditorTool::Eraser if pressed => {
+      editor.paint(&mut maps, &handle.0, pos, None);
+    }
+    EditorTool::Fill if just_pressed => {
+      let Some(map) = maps.get(&handle.0) else {
+        return;
+      };
+      for pos in flood(map, pos) {
+        let cell = editor.brush_cell(pos);
+        editor.paint(&mut maps, &handle.0, pos, Some(cell));
+      }
+    }
+    EditorTool::Rectangle if just_pressed => editor.drag_start = Some(pos),
+    _ => {}
+  }
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+  fn build(&self, app: &mut App) {
+    app.init_resource::<MapEditor>().add_systems(
+      Update,
+      (
+        toggle_editor,
+        (ui::editor_panel, editor_shortcuts, paint_map)
+          .chain()
+          .run_if(editor_open),
+      )
+        .chain()
+        .in_set(GameplaySet),
+    );
+  }
+}
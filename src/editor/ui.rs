@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{egui, EguiContexts};
+
+use super::{EditorTool, MapEditor};
+use crate::{
+  map::{MapData, MapHandle, MapTile},
+  tile::{Tile, TileAtlases, TileType},
+};
+
+/// The size palette previews are drawn at, in points.
+const PREVIEW_SIZE: f32 = 32.0;
+
+/// A palette entry's texture and the part of it showing the tile.
+struct Preview {
+  tile:    MapTile,
+  texture: egui::TextureId,
+  uv:      egui::Rect,
+}
+
+fn previews(
+  contexts: &mut EguiContexts,
+  atlases: &TileAtlases,
+  texture_atlases: &Assets<TextureAtlas>,
+) -> Vec<Preview> {
+  MapTile::palette()
+    .into_iter()
+    .filter_map(|tile| {
+      let atlas = tile.atlas_handle(atlases);
+      let texture_atlas = texture_atlases.get(&atlas.atlas)?;
+      let sprite = Tile::new(tile.clone()).texture_atlas_sprite(atlases);
+      let rect = texture_atlas.textures.get(sprite.index)?;
+      let (min, max) =
+        (rect.min / texture_atlas.size, rect.max / texture_atlas.size);
+      let (left, right) = if sprite.flip_x {
+        (max.x, min.x)
+      } else {
+        (min.x, max.x)
+      };
+      Some(Preview {
+        texture: contexts.add_image(texture_atlas.texture.clone_weak()),
+        uv: egui::Rect::from_min_max(
+          egui::pos2(left, min.y),
+          egui::pos2(right, max.y),
+        ),
+        tile,
+      })
+    })
+    .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn editor_panel(
+  mut contexts: EguiContexts,
+  mut editor: ResMut<MapEditor>,
+  atlases: Res<TileAtlases>,
+  texture_atlases: Res<Assets<TextureAtlas>>,
+  mut maps: ResMut<Assets<MapData>>,
+  handle: Res<MapHandle>,
+  asset_server: Res<AssetServer>,
+) {
+  let previews = previews(&mut contexts, &atlases, &texture_atlases);
+  let editor = &mut *editor;
+  let ctx = contexts.ctx_mut();
+
+  egui::Window::new("Map editor").show(ctx, |ui| {
+    ui.horizontal(|ui| {
+      for tool in EditorTool::ALL {
+        ui.selectable_value(&mut editor.tool, tool, tool.label());
+      }
+    });
+    ui.add(egui::Slider::new(&mut editor.layer, 0..=3).text("layer"));
+    let variants = editor.brush.coords().len().max(1);
+    ui.add(
+      egui::Slider::new(&mut editor.variant, 0..=variants - 1).text("variant"),
+    );
+
+    ui.separator();
+    egui::Grid::new("map editor palette").show(ui, |ui| {
+      for (index, preview) in previews.iter().enumerate() {
+        let image =
+          egui::Image::new(egui::load::SizedTexture::new(preview.texture, [
+            PREVIEW_SIZE,
+            PREVIEW_SIZE,
+          ]))
+          .uv(preview.uv);
+        let button =
+          egui::ImageButton::new(image).selected(editor.brush == preview.tile);
+        if ui
+          .add(button)
+          .on_hover_text(format!("{:?}", preview.tile))
+          .clicked()
+        {
+          editor.brush = preview.tile.clone();
+          editor.variant = 0;
+        }
+        if index % 4 == 3 {
+          ui.end_row();
+        }
+      }
+    });
+
+    ui.separator();
+    ui.horizontal(|ui| {
+      if ui
+        .add_enabled(editor.can_undo(), egui::Button::new("Undo"))
+        .clicked()
+      {
+        editor.undo(&mut maps, &handle.0);
+      }
+      if ui
+        .add_enabled(editor.can_redo(), egui::Button::new("Redo"))
+        .clicked()
+      {
+        editor.redo(&mut maps, &handle.0);
+      }
+      if ui.button("Save").clicked() {
+        editor.save(&maps, &handle.0, &asset_server);
+      }
+      if editor.dirty {
+        ui.label("unsaved changes");
+      }
+    });
+  });
+
+  editor.pointer_over_ui =
+    ctx.wants_pointer_input() || ctx.is_pointer_over_area();
+}
@@ -0,0 +1,84 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::state::EndRun;
+
+/// Named integer variables tracking story progress. A flag that was never set
+/// reads as zero, and a flag counts as set when it is non-zero.
+#[derive(Resource, Clone, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+pub struct GameFlags(pub HashMap<String, i64>);
+
+impl GameFlags {
+  pub fn get(&self, flag: &str) -> i64 {
+    self.0.get(flag).copied().unwrap_or_default()
+  }
+  pub fn is_set(&self, flag: &str) -> bool { self.get(flag) != 0 }
+  pub fn set(&mut self, flag: impl Into<String>, value: i64) {
+    self.0.insert(flag.into(), value);
+  }
+}
+
+/// A test against the current flags, as written in data files.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Condition {
+  Set(String),
+  Unset(String),
+  Equals(String, i64),
+  AtLeast(String, i64),
+  All(Vec<Condition>),
+  Any(Vec<Condition>),
+}
+
+impl Condition {
+  pub fn evaluate(&self, flags: &GameFlags) -> bool {
+    match self {
+      Condition::Set(flag) => flags.is_set(flag),
+      Condition::Unset(flag) => !flags.is_set(flag),
+      Condition::Equals(flag, value) => flags.get(flag) == *value,
+      Condition::AtLeast(flag, value) => flags.get(flag) >= *value,
+      Condition::All(conditions) => {
+        conditions.iter().all(|c| c.evaluate(flags))
+      }
+      Condition::Any(conditions) => {
+        conditions.iter().any(|c| c.evaluate(flags))
+      }
+    }
+  }
+}
+
+/// A change to the flags, as written in data files.
+#[derive(Clone, Debug, Deserialize)]
+pub enum FlagEffect {
+  Set(String, i64),
+  Add(String, i64),
+  Clear(String),
+}
+
+impl FlagEffect {
+  pub fn apply(&self, flags: &mut GameFlags) {
+    match self {
+      FlagEffect::Set(flag, value) => flags.set(flag.clone(), *value),
+      FlagEffect::Add(flag, value) => {
+        let current = flags.get(flag);
+        flags.set(flag.clone(), current + value);
+      }
+      FlagEffect::Clear(flag) => {
+        flags.0.remove(flag);
+      }
+    }
+  }
+}
+
+fn reset_flags(mut flags: ResMut<GameFlags>) { *flags = GameFlags::default(); }
+
+pub struct FlagsPlugin;
+
+impl Plugin for FlagsPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<GameFlags>()
+      .init_resource::<GameFlags>()
+      .add_systems(EndRun, reset_flags);
+  }
+}
@@ -0,0 +1,347 @@
+use std::cmp::Ordering;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+  editor::MapEditor,
+  map::MapTile,
+  player::Player,
+  state::{EndRun, GameplaySet},
+  tile::{Tile, TilePosition},
+};
+
+/// How far players can see, in cells.
+const SIGHT_RADIUS: i64 = 8;
+/// The tint of tiles that have been seen but aren't in sight.
+const REMEMBERED_COLOR: Color = Color::rgb(0.35, 0.35, 0.45);
+
+/// A cell of the map grid. Each cell is two world units across and holds a
+/// ground tile on its even corner and anything above it on its odd one.
+pub type Cell = IVec2;
+
+pub fn tile_cell(pos: &TilePosition) -> Cell {
+  IVec2::new(pos.x.div_euclid(2) as i32, pos.y.div_euclid(2) as i32)
+}
+
+pub fn world_cell(world: Vec2) -> Cell {
+  ((world + Vec2::ONE) / 2.0).floor().as_ivec2()
+}
+
+/// An exact slope, `num / den` with `den > 0`.
+#[derive(Clone, Copy)]
+struct Slope {
+  num: i64,
+  den: i64,
+}
+
+impl Slope {
+  fn new(num: i64, den: i64) -> Self { Slope { num, den } }
+
+  /// `depth * self`, rounded to the nearest column with ties going up.
+  fn round_up(&self, depth: i64) -> i64 {
+    (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+  }
+
+  /// `depth * self`, rounded to the nearest column with ties going down.
+  fn round_down(&self, depth: i64) -> i64 {
+    -(-2 * depth * self.num + self.den).div_euclid(2 * self.den)
+  }
+
+  /// How `col` compares with `depth * self`.
+  fn cmp_col(&self, depth: i64, col: i64) -> Ordering {
+    (col * self.den).cmp(&(depth * self.num))
+  }
+}
+
+/// One of the four quarters of the view around the origin, with `depth`
+/// counting outwards and `col` across.
+#[derive(Clone, Copy)]
+enum Quadrant {
+  North,
+  East,
+  South,
+  West,
+}
+
+impl Quadrant {
+  fn cell(&self, origin: Cell, depth: i64, col: i64) -> Cell {
+    let (depth, col) = (depth as i32, col as i32);
+    match self {
+      Quadrant::North => origin + IVec2::new(col, depth),
+      Quadrant::East => origin + IVec2::new(depth, col),
+      Quadrant::South => origin + IVec2::new(col, -depth),
+      Quadrant::West => origin + IVec2::new(-depth, col),
+    }
+  }
+}
+
+/// The cells visible from `origin` within `radius` cells, using symmetric
+/// shadowcasting: a cell is visible when a line from the centre of the origin
+/// reaches its centre, and opaque cells are visible but hide what's behind
+/// them.
+pub fn field_of_view(
+  origin: Cell,
+  radius: i64,
+  is_opaque: impl Fn(Cell) -> bool,
+) -> HashSet<Cell> {
+  let mut visible = HashSet::default();
+  visible.insert(origin);
+  for quadrant in [
+    Quadrant::North,
+    Quadrant::East,
+    Quadrant::South,
+    Quadrant::West,
+  ] {
+    // rows still to scan, as (depth, start slope, end slope)
+    let mut rows = vec![(1, Slope::new(-1, 1), Slope::new(1, 1))];
+    while let Some((depth, mut start, end)) = rows.pop() {
+      if depth > radius {
+        continue;
+      }
+      let mut prev_opaque = None;
+      for col in start.round_up(depth)..=end.round_down(depth) {
+        let cell = quadrant.cell(origin, depth, col);
+        let opaque = is_opaque(cell);
+        let symmetric =
+          start.cmp_col(depth, col).is_ge() && end.cmp_col(depth, col).is_le();
+        if (opaque || symmetric) && depth * depth + col * col <= radius * radius
+        {
+          visible.insert(cell);
+        }
+        let slope = Slope::new(2 * col - 1, 2 * depth);
+        match (prev_opaque, opaque) {
+          (Some(true), false) => start = slope,
+          (Some(false), true) => rows.push((depth + 1, start, slope)),
+          _ => {}
+        }
+        prev_opaque = Some(opaque);
+      }
+      if prev_opaque == Some(false) {
+        rows.push((depth + 1, start, end));
+      }
+    }
+  }
+  visible
+}
+
+/// The cells holding a tile that blocks sight, and light.
+#[derive(Resource, Default)]
+pub struct Occluders(pub HashSet<Cell>);
+
+/// Which cells the players can see now, and which they've seen this run.
+#[derive(Resource, Default)]
+pub struct FogOfWar {
+  pub visible:  HashSet<Cell>,
+  pub revealed: HashSet<Cell>,
+  /// The cells the players stood in when sight was last computed.
+  viewers:      Vec<Cell>,
+}
+
+/// Rebuilds the occluders when map tiles change.
+#[allow(clippy::type_complexity)]
+fn track_occluders(
+  changed: Query<(), Changed<Tile<MapTile>>>,
+  mut removed: RemovedComponents<Tile<MapTile>>,
+  tiles: Query<(&TilePosition, &Tile<MapTile>)>,
+  mut occluders: ResMut<Occluders>,
+) {
+  if changed.is_empty() && removed.read().count() == 0 {
+    return;
+  }
+  occluders.0 = tiles
+    .iter()
+    .filter(|(_, tile)| !tile.passable())
+    .map(|(pos, _)| tile_cell(pos))
+    .collect();
+}
+
+fn update_sight(
+  players: Query<&GlobalTransform, With<Player>>,
+  occluders: Res<Occluders>,
+  mut fog: ResMut<FogOfWar>,
+) {
+  let viewers = players
+    .iter()
+    .map(|transform| world_cell(transform.translation().xy()))
+    .collect::<Vec<_>>();
+  if viewers == fog.viewers && !occluders.is_changed() {
+    return;
+  }
+
+  let fog = &mut *fog;
+  fog.visible.clear();
+  for &viewer in &viewers {
+    fog
+      .visible
+      .extend(field_of_view(viewer, SIGHT_RADIUS, |cell| {
+        occluders.0.contains(&cell)
+      }));
+  }
+  fog.revealed.extend(fog.visible.iter().copied());
+  fog.viewers = viewers;
+}
+
+/// Hides unseen tiles and dims remembered ones. The map editor sees
+/// everything.
+fn apply_fog(
+  fog: Res<FogOfWar>,
+  editor: Res<MapEditor>,
+  mut tiles: Query<
+    (&TilePosition, &mut Visibility, &mut TextureAtlasSprite),
+    With<Tile<MapTile>>,
+  >,
+) {
+  if !fog.is_changed() && !editor.is_changed() {
+    return;
+  }
+  for (pos, mut visibility, mut sprite) in tiles.iter_mut() {
+    let cell = tile_cell(pos);
+    let (shown, color) = if editor.open || fog.visible.contains(&cell) {
+      (true, Color::WHITE)
+    } else {
+      (fog.revealed.contains(&cell), REMEMBERED_COLOR)
+    };
+    visibility.set_if_neq(if shown {
+      Visibility::Inherited
+    } else {
+      Visibility::Hidden
+    });
+    if sprite.color != color {
+      sprite.color = color;
+    }
+  }
+}
+
+fn reset_fog(mut fog: ResMut<FogOfWar>) { *fog = FogOfWar::default(); }
+
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<Occluders>()
+      .init_resource::<FogOfWar>()
+      .add_systems(Update, track_occluders)
+      .add_systems(
+        Update,
+        (update_sight, apply_fog)
+          .chain()
+          .after(track_occluders)
+          .after(crate::player::apply_movement)
+          .in_set(GameplaySet),
+      )
+      .add_systems(EndRun, reset_fog);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Parses a grid where `#` is opaque and `@` is the origin, with the first
+  /// row at the top. Returns the origin and the opaque cells.
+  fn parse(grid: &[&str]) -> (Cell, HashSet<Cell>) {
+    let mut origin = IVec2::ZERO;
+    let mut opaque = HashSet::default();
+    for (row, line) in grid.iter().enumerate() {
+      for (col, c) in line.chars().enumerate() {
+        let cell = IVec2::new(col as i32, -(row as i32));
+        match c {
+          '#' => {
+            opaque.insert(cell);
+          }
+          '@' => origin = cell,
+          _ => {}
+        }
+      }
+    }
+    (origin, opaque)
+  }
+
+  /// Renders what's visible in a grid, with `.` for hidden cells.
+  fn view(grid: &[&str], radius: i64) -> Vec<String> {
+    let (origin, opaque) = parse(grid);
+    let visible = field_of_view(origin, radius, |cell| opaque.contains(&cell));
+    grid
+      .iter()
+      .enumerate()
+      .map(|(row, line)| {
+        line
+          .chars()
+          .enumerate()
+          .map(|(col, c)| {
+            let cell = IVec2::new(col as i32, -(row as i32));
+            if visible.contains(&cell) {
+              c
+            } else {
+              '.'
+            }
+          })
+          .collect()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn open_ground_is_visible_within_radius() {
+    let grid = ["     ", "     ", "  @  ", "     ", "     "];
+    assert_eq!(view(&grid, 2), [
+      ".. ..", ".   .", "  @  ", ".   .", ".. .."
+    ]);
+  }
+
+  #[test]
+  fn walls_are_visible_and_hide_what_is_behind_them() {
+    let grid = ["       ", "  #    ", "  @    ", "       "];
+    let seen = view(&grid, 10);
+    assert_eq!(seen[1].chars().nth(2), Some('#'));
+    assert_eq!(seen[0].chars().nth(2), Some('.'));
+  }
+
+  #[test]
+  fn corridor_limits_sight_to_its_length() {
+    let grid = ["#########", "#@      #", "#########"];
+    assert_eq!(view(&grid, 20), grid);
+  }
+
+  #[test]
+  fn pillar_casts_a_shadow() {
+    let grid = [
+      "         ",
+      "         ",
+      "    #    ",
+      "         ",
+      "    @    ",
+    ];
+    let seen = view(&grid, 10);
+    assert_eq!(seen[2], "    #    ");
+    // the cells straight behind the pillar are hidden, the sides are not
+    assert_eq!(seen[1].chars().nth(4), Some('.'));
+    assert_eq!(seen[0].chars().nth(4), Some('.'));
+    assert_eq!(seen[0].chars().next(), Some(' '));
+    assert_eq!(seen[0].chars().nth(8), Some(' '));
+  }
+
+  #[test]
+  fn sight_is_symmetric() {
+    let grid = [
+      "   #     ",
+      "     #   ",
+      " #       ",
+      "    @  # ",
+      "  #      ",
+      "      #  ",
+    ];
+    let (origin, opaque) = parse(&grid);
+    let is_opaque = |cell: Cell| opaque.contains(&cell);
+    for target in field_of_view(origin, 10, is_opaque) {
+      if is_opaque(target) {
+        continue;
+      }
+      assert!(
+        field_of_view(target, 10, is_opaque).contains(&origin),
+        "{origin} sees {target} but not the other way around"
+      );
+    }
+  }
+}
@@ -0,0 +1,538 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+  clock::DayEvent,
+  item::{
+    EquipSlot, Equipment, HotbarSelection, Inventory, InventoryChanged,
+    ItemTile, Items, HOTBAR_SLOTS,
+  },
+  player::{Player, PlayerId},
+  quest::{QuestCompleted, QuestStarted, Quests},
+  state::{EndRun, GameplaySet, RunEntity},
+  tile::{Tile, TileAtlases, TileType},
+  vitals::{Health, Stamina},
+};
+
+/// The window height, in physical pixels, that the HUD is drawn at 1:1.
+const BASE_HEIGHT: f32 = 360.0;
+/// The size of an item cell, in art pixels.
+const CELL: f32 = 18.0;
+const CELL_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+const SELECTED_CELL_COLOR: Color = Color::rgba(0.9, 0.8, 0.4, 0.8);
+const BAR_WIDTH: f32 = 64.0;
+const BAR_HEIGHT: f32 = 4.0;
+/// How long a status message stays up, in seconds.
+const STATUS_SECONDS: f32 = 4.0;
+
+/// Logical pixels per art pixel. Always a whole number of physical pixels, so
+/// pixel-art icons stay crisp.
+#[derive(Resource, Clone, Copy, PartialEq)]
+pub struct HudScale(pub f32);
+
+impl Default for HudScale {
+  fn default() -> Self { HudScale(1.0) }
+}
+
+/// The most recent thing worth telling the players about.
+#[derive(Resource, Default)]
+pub struct StatusLine {
+  pub text:      String,
+  pub remaining: f32,
+}
+
+impl StatusLine {
+  pub fn show(&mut self, text: impl Into<String>) {
+    self.text = text.into();
+    self.remaining = STATUS_SECONDS;
+  }
+}
+
+#[derive(Component)]
+struct HudRoot;
+
+#[derive(Component)]
+struct PlayerHud(Entity);
+
+#[derive(Component)]
+struct HealthBar(Entity);
+
+#[derive(Component)]
+struct StaminaBar(Entity);
+
+#[derive(Component)]
+struct EquippedIcon {
+  player: Entity,
+  slot:   EquipSlot,
+}
+
+#[derive(Component)]
+struct HotbarIcon {
+  player: Entity,
+  slot:   usize,
+}
+
+#[derive(Component)]
+struct HotbarCount {
+  player: Entity,
+  slot:   usize,
+}
+
+#[derive(Component)]
+struct StatusText;
+
+fn update_hud_scale(
+  windows: Query<&Window, With<PrimaryWindow>>,
+  mut scale: ResMut<HudScale>,
+) {
+  let Ok(window) = windows.get_single() else {
+    return;
+  };
+  let physical = (window.physical_height() as f32 / BASE_HEIGHT)
+    .floor()
+    .max(1.0);
+  scale.set_if_neq(HudScale(physical / window.scale_factor() as f32));
+}
+
+fn px(scale: HudScale, art_pixels: f32) -> Val { Val::Px(art_pixels * scale.0) }
+
+fn spawn_root(commands: &mut Commands, scale: HudScale) {
+  let root = commands
+    .spawn((
+      NodeBundle {
+        style: Style {
+          position_type: PositionType::Absolute,
+          width: Val::Percent(100.0),
+          flex_direction: FlexDirection::Column,
+          align_items: AlignItems::Center,
+          padding: UiRect::all(px(scale, 2.0)),
+          row_gap: px(scale, 2.0),
+          ..default()
+        },
+        ..default()
+      },
+      Name::new("hud"),
+      HudRoot,
+      RunEntity,
+    ))
+    .id();
+  commands.entity(root).with_children(|root| {
+    root.spawn((
+      NodeBundle {
+        style: Style {
+          width: Val::Percent(100.0),
+          flex_wrap: FlexWrap::Wrap,
+          column_gap: px(scale, 8.0),
+          row_gap: px(scale, 4.0),
+          ..default()
+        },
+        ..default()
+      },
+      Name::new("player huds"),
+    ));
+    root.spawn((
+      TextBundle::from_section("", TextStyle {
+        font_size: 8.0 * scale.0,
+        color: Color::WHITE,
+        ..default()
+      }),
+      StatusText,
+    ));
+  });
+}
+
+fn spawn_bar(
+  parent: &mut ChildBuilder,
+  scale: HudScale,
+  color: Color,
+  marker: impl Component,
+) {
+  parent
+    .spawn(NodeBundle {
+      style: Style {
+        width: px(scale, BAR_WIDTH),
+        height: px(scale, BAR_HEIGHT),
+        ..default()
+      },
+      background_color: CELL_COLOR.into(),
+      ..default()
+    })
+    .with_children(|track| {
+      track.spawn((
+        NodeBundle {
+          style: Style {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+          },
+          background_color: color.into(),
+          ..default()
+        },
+        marker,
+      ));
+    });
+}
+
+fn spawn_cell(
+  parent: &mut ChildBuilder,
+  scale: HudScale,
+  atlas: &Handle<TextureAtlas>,
+  icon: impl Component,
+  count: Option<impl Component>,
+) {
+  parent
+    .spawn(NodeBundle {
+      style: Style {
+        width: px(scale, CELL),
+        height: px(scale, CELL),
+        padding: UiRect::all(px(scale, 1.0)),
+        ..default()
+      },
+      background_color: CELL_COLOR.into(),
+      ..default()
+    })
+    .with_children(|cell| {
+      cell.spawn((
+        AtlasImageBundle {
+          style: Style {
+            width: px(scale, CELL - 2.0),
+            height: px(scale, CELL - 2.0),
+            ..default()
+          },
+          texture_atlas: atlas.clone(),
+          visibility: Visibility::Hidden,
+          ..default()
+        },
+        icon,
+      ));
+      if let Some(count) = count {
+        cell.spawn((
+          TextBundle {
+            style: Style {
+              position_type: PositionType::Absolute,
+              right: px(scale, 1.0),
+              bottom: Val::Px(0.0),
+              ..default()
+            },
+            text: Text::from_section("", TextStyle {
+              font_size: 6.0 * scale.0,
+              color: Color::WHITE,
+              ..default()
+            }),
+            ..default()
+          },
+          count,
+        ));
+      }
+    });
+}
+
+fn spawn_player_hud(
+  parent: &mut ChildBuilder,
+  scale: HudScale,
+  atlases: &TileAtlases,
+  player: Entity,
+  id: PlayerId,
+) {
+  let atlas = ItemTile::default().atlas_handle(atlases).atlas;
+  parent
+    .spawn((
+      NodeBundle {
+        style: Style {
+          flex_direction: FlexDirection::Column,
+          row_gap: px(scale, 1.0),
+          ..default()
+        },
+        ..default()
+      },
+      Name::new(format!("hud: player {}", id.0 + 1)),
+      PlayerHud(player),
+    ))
+    .with_children(|hud| {
+      hud.spawn(TextBundle::from_section(
+        format!("Player {}", id.0 + 1),
+        TextStyle {
+          font_size: 8.0 * scale.0,
+          color: Color::WHITE,
+          ..default()
+        },
+      ));
+      spawn_bar(hud, scale, Color::CRIMSON, HealthBar(player));
+      spawn_bar(hud, scale, Color::GOLD, StaminaBar(player));
+      hud
+        .spawn(NodeBundle {
+          style: Style {
+            column_gap: px(scale, 1.0),
+            ..default()
+          },
+          ..default()
+        })
+        .with_children(|row| {
+          for slot in EquipSlot::ALL {
+            let icon = EquippedIcon { player, slot };
+            spawn_cell(row, scale, &atlas, icon, None::<HotbarCount>);
+          }
+          row.spawn(NodeBundle {
+            style: Style {
+              width: px(scale, 3.0),
+              ..default()
+            },
+            ..default()
+          });
+          for slot in 0..HOTBAR_SLOTS {
+            let icon = HotbarIcon { player, slot };
+            let count = HotbarCount { player, slot };
+            spawn_cell(row, scale, &atlas, icon, Some(count));
+          }
+        });
+    });
+}
+
+/// Gives every player a panel and removes panels whose player is gone. Every
+/// panel is rebuilt when the scale changes.
+fn sync_player_huds(
+  mut commands: Commands,
+  players: Query<(Entity, &PlayerId), With<Player>>,
+  roots: Query<(Entity, &Children), With<HudRoot>>,
+  huds: Query<(Entity, &PlayerHud)>,
+  scale: Res<HudScale>,
+  atlases: Res<TileAtlases>,
+) {
+  let root = roots.get_single().ok();
+  if scale.is_changed() || root.is_none() {
+    if let Some((root, _)) = root {
+      commands.entity(root).despawn_recursive();
+    }
+    // the panels are added next frame, once the new root is in place
+    spawn_root(&mut commands, *scale);
+    return;
+  }
+  let Some((_, children)) = root else {
+    return;
+  };
+  let row = children[0];
+
+  for (entity, hud) in huds.iter() {
+    if players.get(hud.0).is_err() {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+  let mut missing = players
+    .iter()
+    .filter(|(player, _)| !huds.iter().any(|(_, hud)| hud.0 == *player))
+    .collect::<Vec<_>>();
+  missing.sort_by_key(|(_, id)| **id);
+  commands.entity(row).with_children(|row| {
+    for (player, id) in missing {
+      spawn_player_hud(row, *scale, &atlases, player, *id);
+    }
+  });
+}
+
+#[allow(clippy::type_complexity)]
+fn update_vital_bars(
+  players: Query<(Entity, Ref<Health>, Ref<Stamina>)>,
+  new_huds: Query<(), Added<PlayerHud>>,
+  mut health_bars: Query<(&HealthBar, &mut Style), Without<StaminaBar>>,
+  mut stamina_bars: Query<(&StaminaBar, &mut Style), Without<HealthBar>>,
+) {
+  let refresh_all = !new_huds.is_empty();
+  for (player, health, stamina) in players.iter() {
+    if refresh_all || health.is_changed() {
+      for (bar, mut style) in health_bars.iter_mut() {
+        if bar.0 == player {
+          style.width = Val::Percent(100.0 * health.fraction());
+        }
+      }
+    }
+    if refresh_all || stamina.is_changed() {
+      for (bar, mut style) in stamina_bars.iter_mut() {
+        if bar.0 == player {
+          style.width = Val::Percent(100.0 * stamina.fraction());
+        }
+      }
+    }
+  }
+}
+
+fn show_icon(
+  icon: Option<(usize, usize)>,
+  atlases: &TileAtlases,
+  image: &mut UiTextureAtlasImage,
+  visibility: &mut Visibility,
+) {
+  match icon {
+    Some(icon) => {
+      image.index = Tile::new(ItemTile { icon })
+        .texture_atlas_sprite(atlases)
+        .index;
+      *visibility = Visibility::Inherited;
+    }
+    None => *visibility = Visibility::Hidden,
+  }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_item_cells(
+  players: Query<(Entity, Ref<Inventory>, Ref<Equipment>)>,
+  new_huds: Query<(), Added<PlayerHud>>,
+  mut hotbar_icons: Query<
+    (&HotbarIcon, &mut UiTextureAtlasImage, &mut Visibility),
+    Without<EquippedIcon>,
+  >,
+  mut hotbar_counts: Query<(&HotbarCount, &mut Text)>,
+  mut equipped_icons: Query<
+    (&EquippedIcon, &mut UiTextureAtlasImage, &mut Visibility),
+    Without<HotbarIcon>,
+  >,
+  items: Items,
+  atlases: Res<TileAtlases>,
+) {
+  let refresh_all = !new_huds.is_empty();
+  for (player, inventory, equipment) in players.iter() {
+    if refresh_all || inventory.is_changed() {
+      for (cell, mut image, mut visibility) in hotbar_icons.iter_mut() {
+        if cell.player != player {
+          continue;
+        }
+        let stack = inventory.slots.get(cell.slot).and_then(Option::as_ref);
+        let icon = stack
+          .and_then(|stack| items.get(&stack.item))
+          .map(|definition| definition.icon);
+        show_icon(icon, &atlases, &mut image, &mut visibility);
+      }
+      for (cell, mut text) in hotbar_counts.iter_mut() {
+        if cell.player != player {
+          continue;
+        }
+        let stack = inventory.slots.get(cell.slot).and_then(Option::as_ref);
+        text.sections[0].value = match stack {
+          Some(stack) if stack.count > 1 => stack.count.to_string(),
+          _ => String::new(),
+        };
+      }
+    }
+    if refresh_all || equipment.is_changed() {
+      for (cell, mut image, mut visibility) in equipped_icons.iter_mut() {
+        if cell.player != player {
+          continue;
+        }
+        let icon = equipment
+          .get(cell.slot)
+          .and_then(|item| items.get(item))
+          .map(|definition| definition.icon);
+        show_icon(icon, &atlases, &mut image, &mut visibility);
+      }
+    }
+  }
+}
+
+fn collect_status(
+  mut changed: EventReader<InventoryChanged>,
+  mut started: EventReader<QuestStarted>,
+  mut completed: EventReader<QuestCompleted>,
+  mut days: EventReader<DayEvent>,
+  items: Items,
+  quests: Quests,
+  mut status: ResMut<StatusLine>,
+) {
+  for event in changed.read() {
+    if event.delta <= 0 {
+      continue;
+    }
+    let name = items
+      .get(&event.item)
+      .map_or(event.item.0.as_str(), |definition| definition.name.as_str());
+    status.show(format!("+{} {name}", event.delta));
+  }
+  for QuestStarted(quest) in started.read() {
+    let name = quests
+      .get(quest)
+      .map_or(quest.as_str(), |q| q.name.as_str());
+    status.show(format!("Quest started: {name}"));
+  }
+  for QuestCompleted(quest) in completed.read() {
+    let name = quests
+      .get(quest)
+      .map_or(quest.as_str(), |q| q.name.as_str());
+    status.show(format!("Quest complete: {name}"));
+  }
+  for event in days.read() {
+    match event {
+      DayEvent::Dawn { day } => status.show(format!("Day {}", day + 1)),
+      DayEvent::Dusk { .. } => status.show("Night falls"),
+    }
+  }
+}
+
+fn show_status(
+  mut status: ResMut<StatusLine>,
+  mut texts: Query<&mut Text, With<StatusText>>,
+  time: Res<Time>,
+) {
+  if status.remaining > 0.0 {
+    status.remaining -= time.delta_seconds();
+  }
+  let text = if status.remaining > 0.0 {
+    status.text.as_str()
+  } else {
+    ""
+  };
+  for mut section in texts.iter_mut() {
+    if section.sections[0].value != text {
+      section.sections[0].value = text.to_string();
+    }
+  }
+}
+
+fn clear_status(mut status: ResMut<StatusLine>) {
+  *status = StatusLine::default();
+}
+
+/// Lights up the cell of each player's selected hotbar slot.
+fn highlight_selection(
+  players: Query<(Entity, Ref<HotbarSelection>)>,
+  new_huds: Query<(), Added<PlayerHud>>,
+  icons: Query<(&HotbarIcon, &Parent)>,
+  mut cells: Query<&mut BackgroundColor>,
+) {
+  let refresh_all = !new_huds.is_empty();
+  for (player, selection) in players.iter() {
+    if !refresh_all && !selection.is_changed() {
+      continue;
+    }
+    for (icon, cell) in icons.iter().filter(|(icon, _)| icon.player == player) {
+      let Ok(mut background) = cells.get_mut(cell.get()) else {
+        continue;
+      };
+      background.0 = if icon.slot == selection.0 {
+        SELECTED_CELL_COLOR
+      } else {
+        CELL_COLOR
+      };
+    }
+  }
+}
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<HudScale>()
+      .init_resource::<StatusLine>()
+      .add_systems(
+        Update,
+        (
+          update_hud_scale,
+          sync_player_huds,
+          apply_deferred,
+          (update_vital_bars, update_item_cells, highlight_selection),
+          collect_status,
+          show_status,
+        )
+          .chain()
+          .in_set(GameplaySet),
+      )
+      .add_systems(EndRun, clear_status);
+  }
+}
@@ -0,0 +1,292 @@
+use bevy::prelude::*;
+
+use crate::{
+  dialogue::dialogue_open,
+  player::{
+    input::{InputSources, PlayerInput},
+    Player,
+  },
+  state::{GameplaySet, RunEntity},
+};
+
+/// How far, in world units, a player can reach to interact with something.
+const INTERACT_RANGE: f32 = 2.5;
+/// The minimum cosine between the player's facing and the direction to an
+/// interactable for it to count as in front (60 degrees either side).
+const INTERACT_MIN_COS: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, Reflect, PartialEq, Eq, Default)]
+pub enum InteractionKind {
+  #[default]
+  Talk,
+  Read,
+  Open,
+  PickUp,
+}
+
+/// Something in the world a player can interact with.
+#[derive(Component, Clone, Reflect, Default)]
+#[reflect(Component)]
+pub struct Interactable {
+  pub kind:   InteractionKind,
+  /// Shown above the interactable while a player is facing it.
+  pub prompt: String,
+}
+
+impl Interactable {
+  pub fn new(kind: InteractionKind, prompt: impl Into<String>) -> Self {
+    Self {
+      kind,
+      prompt: prompt.into(),
+    }
+  }
+}
+
+/// The interactable a player would use if they pressed interact right now.
+#[derive(Component, Clone, Copy, Reflect, Default)]
+#[reflect(Component)]
+pub struct InteractionFocus(pub Option<Entity>);
+
+/// Sent when a player interacts with something.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InteractionEvent {
+  pub player: Entity,
+  pub target: Entity,
+  pub kind:   InteractionKind,
+}
+
+/// Marks the text showing a player's interaction prompt.
+#[derive(Component)]
+struct InteractionPrompt {
+  player: Entity,
+}
+
+/// Finds the nearest interactable in reach in front of each player.
+pub fn update_interaction_focus(
+  mut players: Query<(&Player, &Transform, &mut InteractionFocus)>,
+  interactables: Query<(Entity, &GlobalTransform), With<Interactable>>,
+) {
+  for (player, transform, mut focus) in players.iter_mut() {
+    let origin = transform.translation.xy();
+    let facing = player.0.facing().as_vec2();
+
+    let nearest = interactables
+      .iter()
+      .filter_map(|(entity, target)| {
+        let offset = target.translation().xy() - origin;
+        let distance = offset.length();
+        let in_front =
+          offset.normalize_or_zero().dot(facing) >= INTERACT_MIN_COS;
+        (distance <= INTERACT_RANGE && in_front).then_some((entity, distance))
+      })
+      .min_by(|(_, a), (_, b)| a.total_cmp(b))
+      .map(|(entity, _)| entity);
+
+    if focus.0 != nearest {
+      focus.0 = nearest;
+    }
+  }
+}
+
+pub fn interact(
+  inputs: InputSources,
+  players: Query<(Entity, &PlayerInput, &InteractionFocus)>,
+  interactables: Query<&Interactable>,
+  mut events: EventWriter<InteractionEvent>,
+) {
+  for (player, input, focus) in players.iter() {
+    if !inputs.interact(input) {
+      continue;
+    }
+    let Some(target) = focus.0 else {
+      continue;
+    };
+    let Ok(interactable) = interactables.get(target) else {
+      continue;
+    };
+    events.send(InteractionEvent {
+      player,
+      target,
+      kind: interactable.kind,
+    });
+  }
+}
+
+/// Keeps one prompt per focused player, floating above the focused target.
+fn show_prompts(
+  mut commands: Commands,
+  players: Query<(Entity, &InteractionFocus)>,
+  interactables: Query<(&Interactable, &GlobalTransform)>,
+  mut prompts: Query<(Entity, &InteractionPrompt, &mut Text, &mut Transform)>,
+) {
+  for (entity, prompt, mut text, mut transform) in prompts.iter_mut() {
+    let target = players
+      .get(prompt.player)
+      .ok()
+      .and_then(|(_, focus)| focus.0)
+      .and_then(|target| interactables.get(target).ok());
+    let Some((interactable, target_transform)) = target else {
+      commands.entity(entity).despawn_recursive();
+      continue;
+    };
+    if text.sections[0].value != interactable.prompt {
+      text.sections[0].value = interactable.prompt.clone();
+    }
+    transform.translation = prompt_translation(target_transform);
+  }
+
+  for (player, focus) in players.iter() {
+    if prompts
+      .iter()
+      .any(|(_, prompt, ..)| prompt.player == player)
+    {
+      continue;
+    }
+    let target = focus.0.and_then(|target| interactables.get(target).ok());
+    let Some((interactable, target_transform)) = target else {
+      continue;
+    };
+    commands.spawn((
+      Text2dBundle {
+        text: Text::from_section(interactable.prompt.clone(), TextStyle {
+          font_size: 16.0,
+          ..default()
+        }),
+        transform: Transform::from_translation(prompt_translation(
+          target_transform,
+        ))
+        .with_scale(Vec3::splat(64.0_f32.recip())),
+        ..default()
+      },
+      Name::new("interaction prompt"),
+      InteractionPrompt { player },
+      RunEntity,
+    ));
+  }
+}
+
+fn prompt_translation(target: &GlobalTransform) -> Vec3 {
+  (target.translation().xy() + Vec2::new(0.0, 1.5)).extend(100.0)
+}
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<Interactable>()
+      .register_type::<InteractionFocus>()
+      .add_event::<InteractionEvent>()
+      .add_systems(
+        Update,
+        (
+          update_interaction_focus,
+          interact.run_if(not(dialogue_open)),
+          show_prompts,
+        )
+          .chain()
+          .after(crate::player::apply_movement)
+          .in_set(GameplaySet),
+      );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use bevy::transform::TransformPlugin;
+
+  use super::*;
+  use crate::{player::status::PlayerStatus, tile::Direction4};
+
+  fn test_app() -> App {
+    let mut app = App::new();
+    app
+      .add_plugins((MinimalPlugins, TransformPlugin))
+      .init_resource::<Input<KeyCode>>()
+      .init_resource::<Input<GamepadButton>>()
+      .init_resource::<Axis<GamepadAxis>>()
+      .add_event::<InteractionEvent>()
+      .add_systems(Update, (update_interaction_focus, interact).chain());
+    app
+  }
+
+  fn spawn_player(app: &mut App, facing: Direction4) -> Entity {
+    app
+      .world
+      .spawn((
+        Player(PlayerStatus::Stand(facing.into())),
+        PlayerInput::default(),
+        InteractionFocus::default(),
+        TransformBundle::default(),
+      ))
+      .id()
+  }
+
+  fn spawn_interactable(app: &mut App, at: Vec2) -> Entity {
+    app
+      .world
+      .spawn((
+        Interactable::new(InteractionKind::Open, "Open"),
+        TransformBundle::from_transform(Transform::from_translation(
+          at.extend(0.0),
+        )),
+      ))
+      .id()
+  }
+
+  fn press_interact(app: &mut App) -> Vec<InteractionEvent> {
+    // let global transforms settle before pressing
+    app.update();
+    app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::E);
+    app.update();
+    app
+      .world
+      .resource_mut::<Input<KeyCode>>()
+      .release(KeyCode::E);
+    let events = app.world.resource::<Events<InteractionEvent>>();
+    events.get_reader().read(events).copied().collect()
+  }
+
+  #[test]
+  fn interacts_with_adjacent_target_in_front() {
+    let mut app = test_app();
+    let player = spawn_player(&mut app, Direction4::East);
+    let target = spawn_interactable(&mut app, Vec2::new(2.0, 0.0));
+
+    assert_eq!(press_interact(&mut app), vec![InteractionEvent {
+      player,
+      target,
+      kind: InteractionKind::Open,
+    }]);
+  }
+
+  #[test]
+  fn ignores_target_behind_player() {
+    let mut app = test_app();
+    spawn_player(&mut app, Direction4::West);
+    spawn_interactable(&mut app, Vec2::new(2.0, 0.0));
+
+    assert!(press_interact(&mut app).is_empty());
+  }
+
+  #[test]
+  fn ignores_target_out_of_reach() {
+    let mut app = test_app();
+    spawn_player(&mut app, Direction4::East);
+    spawn_interactable(&mut app, Vec2::new(INTERACT_RANGE + 1.0, 0.0));
+
+    assert!(press_interact(&mut app).is_empty());
+  }
+
+  #[test]
+  fn picks_nearest_target() {
+    let mut app = test_app();
+    spawn_player(&mut app, Direction4::North);
+    spawn_interactable(&mut app, Vec2::new(0.0, 2.0));
+    let near = spawn_interactable(&mut app, Vec2::new(0.0, 1.0));
+
+    let events = press_interact(&mut app);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].target, near);
+  }
+}
@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{Inventory, InventoryChanged, ItemId, ItemStack, ItemUsed, Items};
+use crate::{
+  player::paperdoll::{PaperdollSlot, SetPaperdollLayer},
+  stats::StatModifiers,
+};
+
+#[derive(
+  Clone, Copy, Debug, Reflect, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum EquipSlot {
+  Weapon,
+  Armor,
+  Accessory,
+}
+
+impl EquipSlot {
+  pub const ALL: [EquipSlot; 3] =
+    [EquipSlot::Weapon, EquipSlot::Armor, EquipSlot::Accessory];
+
+  /// The key this slot's modifiers are stored under in `StatModifiers`.
+  fn modifier_source(&self) -> String {
+    format!("equipment:{self:?}").to_lowercase()
+  }
+
+  fn paperdoll_slot(&self) -> Option<PaperdollSlot> {
+    match self {
+      EquipSlot::Weapon => Some(PaperdollSlot::Weapon),
+      EquipSlot::Armor => Some(PaperdollSlot::Outer),
+      EquipSlot::Accessory => None,
+    }
+  }
+}
+
+/// The items a character is wearing or holding.
+#[derive(Component, Clone, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Equipment {
+  pub weapon:    Option<ItemId>,
+  pub armor:     Option<ItemId>,
+  pub accessory: Option<ItemId>,
+}
+
+impl Equipment {
+  pub fn get(&self, slot: EquipSlot) -> Option<&ItemId> {
+    match slot {
+      EquipSlot::Weapon => self.weapon.as_ref(),
+      EquipSlot::Armor => self.armor.as_ref(),
+      EquipSlot::Accessory => self.accessory.as_ref(),
+    }
+  }
+  pub fn slot_mut(&mut self, slot: EquipSlot) -> &mut Option<ItemId> {
+    match slot {
+      EquipSlot::Weapon => &mut self.weapon,
+      EquipSlot::Armor => &mut self.armor,
+      EquipSlot::Accessory => &mut self.accessory,
+    }
+  }
+}
+
+/// Asks for a slot to be emptied back into the character's inventory.
+#[derive(Event, Clone)]
+pub struct Unequip {
+  pub entity: Entity,
+  pub slot:   EquipSlot,
+}
+
+/// Puts `new` into `slot`, moving the previous item back into the inventory.
+/// Fails, changing nothing, if the inventory has no room for it.
+fn swap_equipped(
+  entity: Entity,
+  slot: EquipSlot,
+  new: Option<ItemId>,
+  equipment: &mut Equipment,
+  inventory: &mut Inventory,
+  changed: &mut EventWriter<InventoryChanged>,
+  items: &Items,
+) -> bool {
+  if let Some(old) = equipment.get(slot).cloned() {
+    let max_stack = items.get(&old).map_or(1, |d| d.max_stack);
+    if inventory.add(&old, 1, max_stack) > 0 {
+      return false;
+    }
+    changed.send(InventoryChanged {
+      entity,
+      item: old,
+      delta: 1,
+    });
+  }
+  *equipment.slot_mut(slot) = new;
+  true
+}
+
+/// Equips used items that have an equipment slot, swapping out whatever was
+/// there before.
+pub fn equip_used_items(
+  mut used: EventReader<ItemUsed>,
+  mut unequips: EventReader<Unequip>,
+  mut query: Query<(&mut Equipment, &mut Inventory)>,
+  mut changed: EventWriter<InventoryChanged>,
+  items: Items,
+) {
+  for ItemUsed {
+    entity,
+    slot: from,
+    item,
+  } in used.read()
+  {
+    let Some(slot) = items.get(item).and_then(|d| d.equip) else {
+      continue;
+    };
+    let Ok((mut equipment, mut inventory)) = query.get_mut(*entity) else {
+      continue;
+    };
+    // take the item out first, so its slot is free for the one it replaces
+    let in_slot = inventory.slots.get(*from).and_then(Option::as_ref);
+    if in_slot.map(|stack| &stack.item) != Some(item) {
+      continue;
+    }
+    inventory.take_one(*from);
+    let equipped = swap_equipped(
+      *entity,
+      slot,
+      Some(item.clone()),
+      &mut equipment,
+      &mut inventory,
+      &mut changed,
+      &items,
+    );
+    if equipped {
+      changed.send(InventoryChanged {
+        entity: *entity,
+        item:   item.clone(),
+        delta:  -1,
+      });
+    } else {
+      // put it back where it came from
+      match &mut inventory.slots[*from] {
+        Some(stack) => stack.count += 1,
+        empty => *empty = Some(ItemStack::new(item.clone(), 1)),
+      }
+    }
+  }
+
+  for Unequip { entity, slot } in unequips.read() {
+    let Ok((mut equipment, mut inventory)) = query.get_mut(*entity) else {
+      continue;
+    };
+    swap_equipped(
+      *entity,
+      *slot,
+      None,
+      &mut equipment,
+      &mut inventory,
+      &mut changed,
+      &items,
+    );
+  }
+}
+
+/// Mirrors equipped items into their wearer's stat modifiers and paperdoll.
+pub fn apply_equipment(
+  mut query: Query<
+    (Entity, &Equipment, &mut StatModifiers),
+    Changed<Equipment>,
+  >,
+  mut layers: EventWriter<SetPaperdollLayer>,
+  items: Items,
+) {
+  for (entity, equipment, mut modifiers) in query.iter_mut() {
+    for slot in EquipSlot::ALL {
+      let definition = equipment.get(slot).and_then(|item| items.get(item));
+      let slot_modifiers =
+        definition.map(|d| d.modifiers.clone()).unwrap_or_default();
+      modifiers.0.insert(slot.modifier_source(), slot_modifiers);
+
+      if let Some(paperdoll_slot) = slot.paperdoll_slot() {
+        layers.send(SetPaperdollLayer {
+          player: entity,
+          slot:   paperdoll_slot,
+          sheet:  definition.and_then(|d| d.paperdoll.clone()),
+          tint:   Color::WHITE,
+        });
+      }
+    }
+  }
+}
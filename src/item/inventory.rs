@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ItemId;
+
+#[derive(
+  Clone, Debug, Default, Reflect, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub struct ItemStack {
+  pub item:  ItemId,
+  pub count: u32,
+}
+
+impl ItemStack {
+  pub fn new(item: impl Into<ItemId>, count: u32) -> Self {
+    Self {
+      item: item.into(),
+      count,
+    }
+  }
+}
+
+/// The number of inventory slots shown on the hotbar, from the first.
+pub const HOTBAR_SLOTS: usize = 8;
+
+/// The hotbar slot a character will use next.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct HotbarSelection(pub usize);
+
+/// A fixed number of slots, each holding at most one stack.
+#[derive(Component, Clone, Debug, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Inventory {
+  pub slots: Vec<Option<ItemStack>>,
+}
+
+impl Default for Inventory {
+  fn default() -> Self { Inventory::new(16) }
+}
+
+impl Inventory {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      slots: vec![None; capacity],
+    }
+  }
+
+  /// Adds up to `count` of `item`, topping up existing stacks before using
+  /// empty slots. Returns how many didn't fit.
+  pub fn add(&mut self, item: &ItemId, mut count: u32, max_stack: u32) -> u32 {
+    let max_stack = max_stack.max(1);
+    for stack in self.slots.iter_mut().flatten() {
+      if count == 0 {
+        break;
+      }
+      if stack.item == *item && stack.count < max_stack {
+        let moved = count.min(max_stack - stack.count);
+        stack.count += moved;
+        count -= moved;
+      }
+    }
+    for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+      if count == 0 {
+        break;
+      }
+      let moved = count.min(max_stack);
+      *slot = Some(ItemStack::new(item.clone(), moved));
+      count -= moved;
+    }
+    count
+  }
+
+  /// Removes up to `count` of `item`, emptying the last stacks first. Returns
+  /// how many were removed.
+  pub fn remove(&mut self, item: &ItemId, count: u32) -> u32 {
+    let mut remaining = count;
+    for slot in self.slots.iter_mut().rev() {
+      if remaining == 0 {
+        break;
+      }
+      let Some(stack) = slot else {
+        continue;
+      };
+      if stack.item != *item {
+        continue;
+      }
+      let moved = remaining.min(stack.count);
+      stack.count -= moved;
+      remaining -= moved;
+      if stack.count == 0 {
+        *slot = None;
+      }
+    }
+    count - remaining
+  }
+
+  /// Takes one item out of the given slot. An empty stack, as an edited save
+  /// can hold, is cleared out instead.
+  pub fn take_one(&mut self, slot: usize) -> Option<ItemId> {
+    let entry = self.slots.get_mut(slot)?;
+    let stack = entry.as_mut()?;
+    let Some(count) = stack.count.checked_sub(1) else {
+      *entry = None;
+      return None;
+    };
+    let item = stack.item.clone();
+    stack.count = count;
+    if stack.count == 0 {
+      *entry = None;
+    }
+    Some(item)
+  }
+
+  pub fn count(&self, item: &ItemId) -> u32 {
+    self
+      .slots
+      .iter()
+      .flatten()
+      .filter(|stack| stack.item == *item)
+      .map(|stack| stack.count)
+      .sum()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn take_one_empties_the_slot_with_the_last_item() {
+    let mut inventory = Inventory::new(2);
+    inventory.slots[0] = Some(ItemStack::new("apple", 2));
+    assert_eq!(inventory.take_one(0), Some(ItemId::from("apple")));
+    assert_eq!(inventory.take_one(0), Some(ItemId::from("apple")));
+    assert_eq!(inventory.slots[0], None);
+    assert_eq!(inventory.take_one(0), None);
+  }
+
+  #[test]
+  fn take_one_clears_an_empty_stack() {
+    let mut inventory = Inventory::new(2);
+    inventory.slots[1] = Some(ItemStack::new("apple", 0));
+    assert_eq!(inventory.take_one(1), None);
+    assert_eq!(inventory.slots[1], None);
+  }
+}
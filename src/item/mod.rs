@@ -0,0 +1,387 @@
+mod equipment;
+mod inventory;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+pub use self::{equipment::*, inventory::*};
+use crate::{
+  data::RonAssetLoader,
+  dialogue::dialogue_open,
+  editor::editor_open,
+  player::input::{InputSources, PlayerInput},
+  state::{GameplaySet, RunEntity},
+  stats::StatModifier,
+  tile::{
+    refresh_tile_sprites, Tile, TileAtlases, TilePosition, TileSheetCoords,
+    TileType,
+  },
+};
+
+/// How close, in world units, a player has to be to collect a pickup.
+const PICKUP_RADIUS: f32 = 0.75;
+
+#[derive(
+  Clone, Debug, Default, Reflect, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ItemId(pub String);
+
+impl From<&str> for ItemId {
+  fn from(value: &str) -> Self { ItemId(value.to_string()) }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemDefinition {
+  pub name:        String,
+  #[serde(default)]
+  pub description: String,
+  #[serde(default = "default_max_stack")]
+  pub max_stack:   u32,
+  /// The cell of this item's icon in the items sheet.
+  pub icon:        (usize, usize),
+  /// Whether using the item uses it up.
+  #[serde(default)]
+  pub consumable:  bool,
+  /// The slot this item is worn in, if it can be equipped.
+  #[serde(default)]
+  pub equip:       Option<EquipSlot>,
+  /// How the item changes its wearer's stats while equipped.
+  #[serde(default)]
+  pub modifiers:   Vec<StatModifier>,
+  /// A sheet laid out like the body sheet, drawn over the wearer.
+  #[serde(default)]
+  pub paperdoll:   Option<String>,
+}
+
+fn default_max_stack() -> u32 { 1 }
+
+/// Every item definition in a `.items.ron` file, by id.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct ItemRegistry {
+  pub items: HashMap<ItemId, ItemDefinition>,
+}
+
+#[derive(Resource)]
+pub struct ItemRegistryHandle(pub Handle<ItemRegistry>);
+
+impl FromWorld for ItemRegistryHandle {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    ItemRegistryHandle(asset_server.load("data/base.items.ron"))
+  }
+}
+
+/// Read access to the loaded item definitions.
+#[derive(SystemParam)]
+pub struct Items<'w> {
+  handle:     Res<'w, ItemRegistryHandle>,
+  registries: Res<'w, Assets<ItemRegistry>>,
+}
+
+impl Items<'_> {
+  pub fn get(&self, item: &ItemId) -> Option<&ItemDefinition> {
+    self.registries.get(&self.handle.0)?.items.get(item)
+  }
+}
+
+/// An item icon, as drawn for pickups lying in the world.
+#[derive(Clone, Default, Reflect)]
+pub struct ItemTile {
+  pub icon: (usize, usize),
+}
+
+impl TileType for ItemTile {
+  fn size_and_center(&self) -> (Vec2, Vec2) { (Vec2::splat(16.0), Vec2::ZERO) }
+  fn coords(&self) -> Vec<TileSheetCoords> {
+    vec![TileSheetCoords::new(self.icon.0, self.icon.1)]
+  }
+  fn atlas_id(&self) -> &str { "items" }
+}
+
+/// A stack of items lying in the world, waiting to be collected.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct ItemPickup(pub ItemStack);
+
+/// Asks for a stack to be put into an entity's inventory. Whatever doesn't
+/// fit is dropped at the entity's feet.
+#[derive(Event, Clone)]
+pub struct AddItem {
+  pub entity: Entity,
+  pub stack:  ItemStack,
+}
+
+/// Asks for a stack to be taken out of an entity's inventory.
+#[derive(Event, Clone)]
+pub struct RemoveItem {
+  pub entity: Entity,
+  pub stack:  ItemStack,
+}
+
+/// Asks for the item in an inventory slot to be used.
+#[derive(Event, Clone)]
+pub struct UseItem {
+  pub entity: Entity,
+  pub slot:   usize,
+}
+
+/// Sent after an item has been used, so gameplay can apply its effect.
+#[derive(Event, Clone)]
+pub struct ItemUsed {
+  pub entity: Entity,
+  /// The inventory slot the item was used from.
+  pub slot:   usize,
+  pub item:   ItemId,
+}
+
+/// Sent whenever the contents of an inventory change.
+#[derive(Event, Clone)]
+pub struct InventoryChanged {
+  pub entity: Entity,
+  pub item:   ItemId,
+  pub delta:  i64,
+}
+
+pub fn spawn_pickup(
+  commands: &mut Commands,
+  atlases: &TileAtlases,
+  stack: ItemStack,
+  pos: TilePosition,
+) -> Entity {
+  let tile_type = ItemTile::default();
+  commands
+    .spawn((
+      SpriteSheetBundle {
+        texture_atlas: tile_type.atlas_handle(atlases).atlas,
+        transform: pos.transform(&tile_type),
+        // hidden until the item's icon is known
+        visibility: Visibility::Hidden,
+        ..Default::default()
+      },
+      Name::new(format!("pickup: {}", stack.item.0)),
+      ItemPickup(stack),
+      pos,
+      RunEntity,
+    ))
+    .id()
+}
+
+/// Gives pickups their icon once the item definitions are available.
+fn attach_pickup_sprites(
+  mut commands: Commands,
+  mut query: Query<
+    (
+      Entity,
+      &ItemPickup,
+      &mut TextureAtlasSprite,
+      &mut Visibility,
+    ),
+    Without<Tile<ItemTile>>,
+  >,
+  items: Items,
+  atlases: Res<TileAtlases>,
+) {
+  for (entity, pickup, mut sprite, mut visibility) in query.iter_mut() {
+    let Some(definition) = items.get(&pickup.0.item) else {
+      continue;
+    };
+    let tile = Tile::new(ItemTile {
+      icon: definition.icon,
+    });
+    *sprite = tile.texture_atlas_sprite(&atlases);
+    *visibility = Visibility::Inherited;
+    commands.entity(entity).insert(tile);
+  }
+}
+
+fn collect_pickups(
+  mut commands: Commands,
+  mut collectors: Query<(Entity, &Transform, &mut Inventory)>,
+  mut pickups: Query<(Entity, &Transform, &mut ItemPickup)>,
+  mut changed: EventWriter<InventoryChanged>,
+  items: Items,
+) {
+  for (collector, collector_transform, mut inventory) in collectors.iter_mut() {
+    for (pickup_entity, pickup_transform, mut pickup) in pickups.iter_mut() {
+      let distance = collector_transform
+        .translation
+        .xy()
+        .distance(pickup_transform.translation.xy());
+      if pickup.0.count == 0 || distance > PICKUP_RADIUS {
+        continue;
+      }
+      let Some(definition) = items.get(&pickup.0.item) else {
+        continue;
+      };
+
+      let leftover =
+        inventory.add(&pickup.0.item, pickup.0.count, definition.max_stack);
+      let taken = pickup.0.count - leftover;
+      if taken == 0 {
+        continue;
+      }
+      changed.send(InventoryChanged {
+        entity: collector,
+        item:   pickup.0.item.clone(),
+        delta:  taken as i64,
+      });
+      pickup.0.count = leftover;
+      if leftover == 0 {
+        commands.entity(pickup_entity).despawn_recursive();
+      }
+    }
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_inventory_events(
+  mut commands: Commands,
+  mut adds: EventReader<AddItem>,
+  mut removes: EventReader<RemoveItem>,
+  mut uses: EventReader<UseItem>,
+  mut inventories: Query<(&mut Inventory, Option<&Transform>)>,
+  mut changed: EventWriter<InventoryChanged>,
+  mut used: EventWriter<ItemUsed>,
+  items: Items,
+  atlases: Res<TileAtlases>,
+) {
+  for AddItem { entity, stack } in adds.read() {
+    let Ok((mut inventory, transform)) = inventories.get_mut(*entity) else {
+      continue;
+    };
+    let Some(definition) = items.get(&stack.item) else {
+      warn!("tried to add unknown item {:?}", stack.item.0);
+      continue;
+    };
+
+    let leftover =
+      inventory.add(&stack.item, stack.count, definition.max_stack);
+    if leftover < stack.count {
+      changed.send(InventoryChanged {
+        entity: *entity,
+        item:   stack.item.clone(),
+        delta:  (stack.count - leftover) as i64,
+      });
+    }
+    let Some(transform) = transform.filter(|_| leftover > 0) else {
+      continue;
+    };
+    let pos = TilePosition::new(
+      transform.translation.x.round() as i64,
+      transform.translation.y.round() as i64,
+      1,
+    );
+    spawn_pickup(
+      &mut commands,
+      &atlases,
+      ItemStack::new(stack.item.clone(), leftover),
+      pos,
+    );
+  }
+
+  for RemoveItem { entity, stack } in removes.read() {
+    let Ok((mut inventory, _)) = inventories.get_mut(*entity) else {
+      continue;
+    };
+    let removed = inventory.remove(&stack.item, stack.count);
+    if removed > 0 {
+      changed.send(InventoryChanged {
+        entity: *entity,
+        item:   stack.item.clone(),
+        delta:  -(removed as i64),
+      });
+    }
+  }
+
+  for UseItem { entity, slot } in uses.read() {
+    let Ok((mut inventory, _)) = inventories.get_mut(*entity) else {
+      continue;
+    };
+    let Some(Some(stack)) = inventory.slots.get(*slot) else {
+      continue;
+    };
+    let item = stack.item.clone();
+    let consumable = items.get(&item).is_some_and(|d| d.consumable);
+    if consumable {
+      inventory.take_one(*slot);
+      changed.send(InventoryChanged {
+        entity: *entity,
+        item:   item.clone(),
+        delta:  -1,
+      });
+    }
+    used.send(ItemUsed {
+      entity: *entity,
+      slot: *slot,
+      item,
+    });
+  }
+}
+
+/// Moves each player's hotbar selection along and uses the selected item.
+fn hotbar_input(
+  inputs: InputSources,
+  mut players: Query<(Entity, &PlayerInput, &mut HotbarSelection)>,
+  mut uses: EventWriter<UseItem>,
+) {
+  for (entity, input, mut selection) in players.iter_mut() {
+    if inputs.next_slot(input) {
+      selection.0 = (selection.0 + 1) % HOTBAR_SLOTS;
+    }
+    if inputs.use_item(input) {
+      uses.send(UseItem {
+        entity,
+        slot: selection.0,
+      });
+    }
+  }
+}
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<ItemId>()
+      .register_type::<ItemStack>()
+      .register_type::<Inventory>()
+      .register_type::<HotbarSelection>()
+      .register_type::<ItemPickup>()
+      .register_type::<Equipment>()
+      .register_type::<Tile<ItemTile>>()
+      .init_asset::<ItemRegistry>()
+      .register_asset_loader(RonAssetLoader::<ItemRegistry>::new(&[
+        "items.ron",
+      ]))
+      .init_resource::<ItemRegistryHandle>()
+      .add_event::<AddItem>()
+      .add_event::<RemoveItem>()
+      .add_event::<UseItem>()
+      .add_event::<ItemUsed>()
+      .add_event::<InventoryChanged>()
+      .add_event::<Unequip>()
+      .add_systems(
+        Update,
+        (
+          attach_pickup_sprites,
+          collect_pickups.after(crate::player::apply_movement),
+          (
+            hotbar_input
+              .run_if(not(dialogue_open))
+              .run_if(not(editor_open)),
+            apply_inventory_events,
+            equip_used_items,
+            apply_equipment,
+          )
+            .chain(),
+        )
+          .in_set(GameplaySet),
+      )
+      .add_systems(
+        Update,
+        refresh_tile_sprites::<ItemTile>
+          .run_if(resource_exists_and_changed::<TileAtlases>()),
+      );
+  }
+}
@@ -0,0 +1,539 @@
+use bevy::{
+  prelude::*,
+  render::{
+    render_resource::{
+      AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat,
+    },
+    texture::ImageSampler,
+    view::NoFrustumCulling,
+  },
+  sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle},
+};
+
+use crate::{
+  editor::MapEditor,
+  fog::{field_of_view, world_cell, Occluders},
+  player::Player,
+  state::{RunEntity, StartRun},
+};
+
+/// The width and height of the light map, in cells. Everything outside it is
+/// fully lit.
+const LIGHT_MAP_CELLS: u32 = 128;
+/// Where the darkness sorts among the sprites, which is above all of them.
+const LIGHT_MAP_Z: f32 = 100.0;
+
+/// Lights up the cells around this entity that aren't hidden behind walls.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct LightSource {
+  /// How far the light reaches, in cells.
+  pub radius:    f32,
+  /// The light added at the centre, where 1.0 is fully lit.
+  pub intensity: f32,
+}
+
+impl Default for LightSource {
+  fn default() -> Self {
+    LightSource {
+      radius:    6.0,
+      intensity: 1.0,
+    }
+  }
+}
+
+/// The light everywhere, before any light sources.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct AmbientLight2d {
+  /// How lit everything is, from 0.0 for fully dark to 1.0 for fully lit.
+  pub brightness: f32,
+  /// The colour of the darkness.
+  pub tint:       Color,
+}
+
+impl Default for AmbientLight2d {
+  fn default() -> Self {
+    AmbientLight2d {
+      brightness: 0.3,
+      tint:       Color::rgb(0.01, 0.01, 0.05),
+    }
+  }
+}
+
+/// A texture with one pixel per cell, drawn over the scene as darkness with
+/// linear filtering so shadows have soft edges.
+#[derive(Resource)]
+pub struct LightMap {
+  pub image:    Handle<Image>,
+  pub material: Handle<LightingMaterial>,
+}
+
+/// Darkens the view of every camera by the light map, in a fullscreen pass
+/// that looks each pixel's world position up in the map.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct LightingMaterial {
+  /// The world area the light map covers, as its bottom-left corner and then
+  /// its size.
+  #[uniform(0)]
+  pub bounds:    Vec4,
+  #[texture(1)]
+  #[sampler(2)]
+  pub light_map: Handle<Image>,
+}
+
+impl Material2d for LightingMaterial {
+  fn vertex_shader() -> ShaderRef { "shaders/lighting.wgsl".into() }
+  fn fragment_shader() -> ShaderRef { "shaders/lighting.wgsl".into() }
+}
+
+impl FromWorld for LightMap {
+  fn from_world(world: &mut World) -> Self {
+    let mut image = Image::new_fill(
+      Extent3d {
+        width:                 LIGHT_MAP_CELLS,
+        height:                LIGHT_MAP_CELLS,
+        depth_or_array_layers: 1,
+      },
+      TextureDimension::D2,
+      &[0, 0, 0, 0],
+      TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler = ImageSampler::linear();
+    let image = world.resource_mut::<Assets<Image>>().add(image);
+    // pixel centres sit on the even ground tile coordinates
+    let size = LIGHT_MAP_CELLS as f32 * 2.0;
+    let corner = -(LIGHT_MAP_CELLS as f32) - 1.0;
+    let material =
+      world
+        .resource_mut::<Assets<LightingMaterial>>()
+        .add(LightingMaterial {
+          bounds:    Vec4::new(corner, corner, size, size),
+          light_map: image.clone(),
+        });
+    LightMap { image, material }
+  }
+}
+
+#[derive(Component)]
+struct LightMapOverlay;
+
+fn spawn_light_map(
+  mut commands: Commands,
+  light_map: Res<LightMap>,
+  mut meshes: ResMut<Assets<Mesh>>,
+) {
+  commands.spawn((
+    MaterialMesh2dBundle {
+      // the shader stretches this over the whole view of each camera
+      mesh: meshes.add(shape::Quad::new(Vec2::splat(2.0)).into()).into(),
+      material: light_map.material.clone(),
+      transform: Transform::from_xyz(0.0, 0.0, LIGHT_MAP_Z),
+      ..default()
+    },
+    NoFrustumCulling,
+    Name::new("light map"),
+    LightMapOverlay,
+    RunEntity,
+  ));
+}
+
+fn light_players(
+  mut commands: Commands,
+  players: Query<Entity, (With<Player>, Without<LightSource>)>,
+) {
+  for player in players.iter() {
+    commands.entity(player).insert(LightSource::default());
+  }
+}
+
+/// The light reaching each cell of a square of `LIGHT_MAP_CELLS` cells
+/// centred on the origin, as rows from the top.
+fn compute_light(
+  lights: &[(Vec2, LightSource)],
+  ambient: f32,
+  occluders: &Occluders,
+) -> Vec<f32> {
+  let cells = LIGHT_MAP_CELLS as usize;
+  let half = (LIGHT_MAP_CELLS / 2) as i32;
+  let mut light = vec![ambient; cells * cells];
+  for (position, source) in lights {
+    let origin = world_cell(*position);
+    let radius = source.radius.ceil() as i64;
+    let lit = field_of_view(origin, radius, |cell| occluders.0.contains(&cell));
+    for cell in lit {
+      let (x, y) = (cell.x + half, half - 1 - cell.y);
+      if !(0..half * 2).contains(&x) || !(0..half * 2).contains(&y) {
+        continue;
+      }
+      // distances are in cells, which are two world units across
+      let distance = (cell.as_vec2() * 2.0).distance(*position) / 2.0;
+      let falloff = (1.0 - distance / source.radius).max(0.0);
+      light[y as usize * cells + x as usize] +=
+        source.intensity * falloff * falloff;
+    }
+  }
+  light
+}
+
+/// Redraws the light map when a light moves or the walls change.
+#[allow(clippy::type_complexity)]
+fn update_light_map(
+  lights: Query<(Ref<GlobalTransform>, Ref<LightSource>)>,
+  mut removed: RemovedComponents<LightSource>,
+  ambient: Res<AmbientLight2d>,
+  occluders: Res<Occluders>,
+  light_map: Res<LightMap>,
+  mut images: ResMut<Assets<Image>>,
+  mut materials: ResMut<Assets<LightingMaterial>>,
+) {
+  let changed = lights
+    .iter()
+    .any(|(transform, light)| transform.is_changed() || light.is_changed());
+  if !changed
+    && removed.read().count() == 0
+    && !ambient.is_changed()
+    && !occluders.is_changed()
+  {
+    return;
+  }
+
+  let lights = lights
+    .iter()
+    .map(|(transform, light)| (transform.translation().xy(), *light))
+    .collect::<Vec<_>>();
+  let light = compute_light(&lights, ambient.brightness, &occluders);
+  let [r, g, b, _] = ambient.tint.as_rgba_u8();
+  let Some(image) = images.get_mut(&light_map.image) else {
+    return;
+  };
+  for (pixel, light) in image.data.chunks_exact_mut(4).zip(light) {
+    let darkness = ((1.0 - light.clamp(0.0, 1.0)) * 255.0) as u8;
+    pixel.copy_from_slice(&[r, g, b, darkness]);
+  }
+  // the material only rebuilds its bind group, and so picks up the new
+  // texture, when it changes too
+  materials.get_mut(&light_map.material);
+}
+
+/// Lights everything up while the map editor is open.
+fn toggle_light_map(
+  editor: Res<MapEditor>,
+  mut overlays: Query<&mut Visibility, With<LightMapOverlay>>,
+) {
+  for mut visibility in overlays.iter_mut() {
+    visibility.set_if_neq(if editor.open {
+      Visibility::Hidden
+    } else {
+      Visibility::Inherited
+    });
+  }
+}
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<LightSource>()
+      .register_type::<AmbientLight2d>()
+      .add_plugins(Material2dPlugin::<LightingMaterial>::default())
+      .init_resource::<AmbientLight2d>()
+      .init_resource::<LightMap>()
+      .add_systems(StartRun, spawn_light_map)
+      .add_systems(
+        Update,
+        (light_players, update_light_map, toggle_light_map)
+          .after(crate::player::apply_movement),
+      );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+  };
+
+  use bevy::{
+    app::PluginsState,
+    audio::AudioPlugin,
+    core_pipeline::clear_color::ClearColorConfig,
+    ecs::system::RunSystemOnce,
+    render::{
+      camera::RenderTarget,
+      extract_resource::{ExtractResource, ExtractResourcePlugin},
+      pipelined_rendering::PipelinedRenderingPlugin,
+      render_asset::RenderAssets,
+      render_resource::{
+        BufferDescriptor, BufferUsages, CommandEncoderDescriptor,
+        ImageCopyBuffer, ImageDataLayout, MapMode, TextureUsages,
+      },
+      renderer::{RenderDevice, RenderQueue},
+      texture::{CompressedImageFormats, ImageType},
+      Render, RenderApp, RenderSet,
+    },
+    tasks::tick_global_task_pools_on_main_thread,
+    window::ExitCondition,
+    winit::WinitPlugin,
+  };
+
+  use super::*;
+  use crate::fog::Cell;
+
+  const AMBIENT: f32 = 0.3;
+
+  /// The width and height of the screenshot, in pixels and world units.
+  const FRAME: u32 = 64;
+  /// Where the screenshot is taken from: far enough right that the right
+  /// quarter of it is past the edge of the light map.
+  const CAMERA_X: f32 = 111.0;
+  const CLEAR: Color = Color::rgb(0.9, 0.8, 0.6);
+  const TINT: [u8; 3] = [3, 3, 13];
+  /// The darkness of the top half of the light map and then the bottom half.
+  const DARKNESS: [u8; 2] = [178, 64];
+  /// How far a channel of the screenshot can be from the reference.
+  const TOLERANCE: u8 = 3;
+  /// What the GPU should draw. Run the test with `UPDATE_SCREENSHOTS` set to
+  /// write the screenshot here instead of comparing against it.
+  const REFERENCE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/screenshots/lighting.png"
+  );
+  /// Where a screenshot that doesn't match the reference is written.
+  const MISMATCH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/target/screenshots/lighting.png"
+  );
+
+  /// The camera's target and the last frame rendered to it, copied back from
+  /// the GPU.
+  #[derive(Resource, Clone, ExtractResource)]
+  struct Screenshot {
+    image:  Handle<Image>,
+    pixels: Arc<Mutex<Option<Vec<u8>>>>,
+  }
+
+  fn copy_screenshot(
+    screenshot: Res<Screenshot>,
+    images: Res<RenderAssets<Image>>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+  ) {
+    let Some(image) = images.get(&screenshot.image) else {
+      return;
+    };
+    // rows of 64 pixels already meet the 256 byte alignment of copies
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label:              Some("screenshot"),
+      size:               (FRAME * FRAME * 4) as u64,
+      usage:              BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+      mapped_at_creation: false,
+    });
+    let mut encoder =
+      device.create_command_encoder(&CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+      image.texture.as_image_copy(),
+      ImageCopyBuffer {
+        buffer: &buffer,
+        layout: ImageDataLayout {
+          offset:         0,
+          bytes_per_row:  Some(FRAME * 4),
+          rows_per_image: None,
+        },
+      },
+      frame_size(),
+    );
+    queue.submit([encoder.finish()]);
+    let slice = buffer.slice(..);
+    device.map_buffer(&slice, MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    *screenshot.pixels.lock().unwrap() =
+      Some(slice.get_mapped_range().to_vec());
+  }
+
+  fn frame_size() -> Extent3d {
+    Extent3d {
+      width:                 FRAME,
+      height:                FRAME,
+      depth_or_array_layers: 1,
+    }
+  }
+
+  fn save_screenshot(pixels: Vec<u8>, path: &str) {
+    let image = Image::new(
+      frame_size(),
+      TextureDimension::D2,
+      pixels,
+      TextureFormat::Rgba8UnormSrgb,
+    );
+    std::fs::create_dir_all(std::path::Path::new(path).parent().unwrap())
+      .unwrap();
+    image.try_into_dynamic().unwrap().save(path).unwrap();
+  }
+
+  /// A headless app that renders a camera to an image and copies every frame
+  /// back into `Screenshot`.
+  fn screenshot_app() -> (App, Screenshot) {
+    let mut app = App::new();
+    app
+      .add_plugins(
+        DefaultPlugins
+          .build()
+          .disable::<WinitPlugin>()
+          .disable::<AudioPlugin>()
+          // render each frame in `update`, before it returns
+          .disable::<PipelinedRenderingPlugin>()
+          .set(WindowPlugin {
+            primary_window:       None,
+            exit_condition:       ExitCondition::DontExit,
+            close_when_requested: false,
+          }),
+      )
+      .add_plugins((
+        Material2dPlugin::<LightingMaterial>::default(),
+        ExtractResourcePlugin::<Screenshot>::default(),
+      ))
+      .insert_resource(Msaa::Off)
+      .init_resource::<LightMap>();
+    app.sub_app_mut(RenderApp).add_systems(
+      Render,
+      copy_screenshot
+        .after(RenderSet::Render)
+        .before(RenderSet::Cleanup),
+    );
+    while app.plugins_state() == PluginsState::Adding {
+      tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    let mut target = Image::new_fill(
+      frame_size(),
+      TextureDimension::D2,
+      &[0, 0, 0, 0],
+      TextureFormat::Rgba8UnormSrgb,
+    );
+    target.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+      | TextureUsages::COPY_SRC
+      | TextureUsages::COPY_DST
+      | TextureUsages::RENDER_ATTACHMENT;
+    let screenshot = Screenshot {
+      image:  app.world.resource_mut::<Assets<Image>>().add(target),
+      pixels: default(),
+    };
+    app.insert_resource(screenshot.clone());
+    (app, screenshot)
+  }
+
+  #[test]
+  #[ignore = "needs a GPU; CI runs it on lavapipe with --include-ignored"]
+  fn the_lighting_pass_darkens_the_view_by_the_light_map() {
+    let (mut app, screenshot) = screenshot_app();
+    let mut camera = Camera2dBundle {
+      camera: Camera {
+        target: RenderTarget::Image(screenshot.image.clone()),
+        ..default()
+      },
+      camera_2d: Camera2d {
+        clear_color: ClearColorConfig::Custom(CLEAR),
+      },
+      ..default()
+    };
+    camera.transform.translation.x = CAMERA_X;
+    app.world.spawn(camera);
+
+    let image = app.world.resource::<LightMap>().image.clone();
+    let mut images = app.world.resource_mut::<Assets<Image>>();
+    let image = images.get_mut(&image).unwrap();
+    let half = LIGHT_MAP_CELLS as usize / 2;
+    for (y, row) in image.data.chunks_exact_mut(half * 8).enumerate() {
+      let darkness = DARKNESS[(y >= half) as usize];
+      for pixel in row.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[TINT[0], TINT[1], TINT[2], darkness]);
+      }
+    }
+    app.world.run_system_once(spawn_light_map);
+
+    // until the shader loads and its pipeline compiles, the frame is just
+    // the clear colour, the same in the darkened top left corner as in the
+    // top right one past the light map
+    let row = FRAME as usize * 4;
+    let pixels = (0..1000)
+      .find_map(|_| {
+        app.update();
+        thread::sleep(Duration::from_millis(10));
+        screenshot
+          .pixels
+          .lock()
+          .unwrap()
+          .take()
+          .filter(|pixels| pixels[..4] != pixels[row - 4..row])
+      })
+      .expect("the lighting pass never drew");
+
+    if std::env::var_os("UPDATE_SCREENSHOTS").is_some() {
+      save_screenshot(pixels, REFERENCE);
+      return;
+    }
+    let reference = Image::from_buffer(
+      &std::fs::read(REFERENCE).unwrap(),
+      ImageType::Extension("png"),
+      CompressedImageFormats::NONE,
+      true,
+      ImageSampler::Default,
+    )
+    .unwrap();
+    assert_eq!(reference.data.len(), pixels.len(), "the reference's size");
+    let off = pixels
+      .iter()
+      .zip(&reference.data)
+      .filter(|(actual, expected)| actual.abs_diff(**expected) > TOLERANCE)
+      .count();
+    if off > 0 {
+      save_screenshot(pixels, MISMATCH);
+      panic!("{off} channels are off the reference; see {MISMATCH}");
+    }
+  }
+
+  /// The light `compute_light` gave `cell`.
+  fn light_at(light: &[f32], cell: Cell) -> f32 {
+    let half = (LIGHT_MAP_CELLS / 2) as i32;
+    let (x, y) = (cell.x + half, half - 1 - cell.y);
+    light[y as usize * LIGHT_MAP_CELLS as usize + x as usize]
+  }
+
+  #[test]
+  fn without_lights_everything_gets_ambient() {
+    let light = compute_light(&[], AMBIENT, &Occluders::default());
+    assert!(light.iter().all(|&cell| cell == AMBIENT));
+  }
+
+  #[test]
+  fn light_fades_out_to_its_radius() {
+    let lights = [(Vec2::ZERO, LightSource::default())];
+    let light = compute_light(&lights, AMBIENT, &Occluders::default());
+    let fade = [0, 2, 5, 7].map(|x| light_at(&light, Cell::new(x, 0)));
+    assert_eq!(fade[0], AMBIENT + 1.0);
+    assert!(
+      fade[0] > fade[1] && fade[1] > fade[2] && fade[2] > AMBIENT,
+      "light fell off as {fade:?}"
+    );
+    assert_eq!(fade[3], AMBIENT);
+  }
+
+  #[test]
+  fn walls_cast_shadows() {
+    let lights = [(Vec2::ZERO, LightSource::default())];
+    let wall = Occluders([Cell::new(2, 0)].into_iter().collect());
+    let light = compute_light(&lights, AMBIENT, &wall);
+    assert!(
+      light_at(&light, Cell::new(2, 0)) > AMBIENT,
+      "the wall is lit"
+    );
+    assert_eq!(light_at(&light, Cell::new(4, 0)), AMBIENT);
+    assert!(light_at(&light, Cell::new(0, 4)) > AMBIENT);
+  }
+}
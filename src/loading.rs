@@ -0,0 +1,153 @@
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::state::AppState;
+
+/// Assets that must finish loading before the game leaves `Loading`. Plugins
+/// add their handles when `Loading` is entered.
+#[derive(Resource, Default)]
+pub struct LoadingAssets(pub Vec<UntypedHandle>);
+
+impl LoadingAssets {
+  pub fn add(&mut self, handle: UntypedHandle) { self.0.push(handle); }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+pub struct LoadingBar;
+
+#[derive(Component)]
+pub struct LoadingText;
+
+fn describe(handle: &UntypedHandle) -> String {
+  handle
+    .path()
+    .map(|path| path.to_string())
+    .unwrap_or_else(|| format!("{:?}", handle.id()))
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+  commands
+    .spawn((
+      NodeBundle {
+        style: Style {
+          width: Val::Percent(100.0),
+          height: Val::Percent(100.0),
+          flex_direction: FlexDirection::Column,
+          align_items: AlignItems::Center,
+          justify_content: JustifyContent::Center,
+          row_gap: Val::Px(12.0),
+          ..default()
+        },
+        background_color: Color::BLACK.into(),
+        z_index: ZIndex::Global(100),
+        ..default()
+      },
+      Name::new("loading screen"),
+      LoadingScreen,
+    ))
+    .with_children(|screen| {
+      screen.spawn((
+        TextBundle::from_section("Loading", TextStyle {
+          font_size: 24.0,
+          color: Color::WHITE,
+          ..default()
+        }),
+        LoadingText,
+      ));
+      screen
+        .spawn(NodeBundle {
+          style: Style {
+            width: Val::Px(240.0),
+            height: Val::Px(12.0),
+            ..default()
+          },
+          background_color: Color::DARK_GRAY.into(),
+          ..default()
+        })
+        .with_children(|track| {
+          track.spawn((
+            NodeBundle {
+              style: Style {
+                width: Val::Percent(0.0),
+                height: Val::Percent(100.0),
+                ..default()
+              },
+              background_color: Color::WHITE.into(),
+              ..default()
+            },
+            LoadingBar,
+          ));
+        });
+    });
+}
+
+fn despawn_loading_screen(
+  mut commands: Commands,
+  query: Query<Entity, With<LoadingScreen>>,
+) {
+  for entity in query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+/// Moves on to the main menu once everything has loaded, or lists what failed
+/// and stays put.
+pub fn check_loading(
+  loading: Res<LoadingAssets>,
+  asset_server: Res<AssetServer>,
+  mut bars: Query<&mut Style, With<LoadingBar>>,
+  mut texts: Query<&mut Text, With<LoadingText>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  let mut loaded = 0;
+  let mut failed = Vec::new();
+  for handle in &loading.0 {
+    match asset_server.get_load_state(handle.id()) {
+      Some(LoadState::Loaded) => loaded += 1,
+      Some(LoadState::Failed) => failed.push(describe(handle)),
+      _ => {}
+    }
+  }
+
+  let total = loading.0.len();
+  for mut style in bars.iter_mut() {
+    style.width = Val::Percent(100.0 * loaded as f32 / total.max(1) as f32);
+  }
+
+  if !failed.is_empty() {
+    for mut text in texts.iter_mut() {
+      let message = format!(
+        "Failed to load {} asset(s):\n{}",
+        failed.len(),
+        failed.join("\n")
+      );
+      if text.sections[0].value != message {
+        error!("{message}");
+        text.sections[0].value = message;
+        text.sections[0].style.color = Color::RED;
+      }
+    }
+    return;
+  }
+
+  for mut text in texts.iter_mut() {
+    text.sections[0].value = format!("Loading {loaded}/{total}");
+  }
+  if loaded == total {
+    next.set(AppState::MainMenu);
+  }
+}
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<LoadingAssets>()
+      .add_systems(OnEnter(AppState::Loading), spawn_loading_screen)
+      .add_systems(OnExit(AppState::Loading), despawn_loading_screen)
+      .add_systems(Update, check_loading.run_if(in_state(AppState::Loading)));
+  }
+}
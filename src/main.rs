@@ -1,7 +1,27 @@
+pub mod audio;
 pub mod camera;
+pub mod clock;
+pub mod data;
+pub mod dialogue;
+pub mod editor;
+pub mod flags;
+pub mod fog;
+pub mod hud;
+pub mod interaction;
+pub mod item;
+pub mod lighting;
+pub mod loading;
 pub mod map;
+pub mod minimap;
+pub mod particles;
 pub mod player;
+pub mod quest;
+pub mod save;
+pub mod state;
+pub mod stats;
+pub mod terrain;
 pub mod tile;
+pub mod vitals;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
@@ -11,10 +31,33 @@ fn main() {
     .add_plugins((
       DefaultPlugins.set(ImagePlugin::default_nearest()),
       WorldInspectorPlugin::default(),
+    ))
+    .add_plugins((
+      state::StatePlugin,
+      loading::LoadingPlugin,
       tile::TilePlugin,
+      item::ItemPlugin,
+      stats::StatsPlugin,
       map::MapPlugin,
+      terrain::TerrainPlugin,
       player::PlayerPlugin,
       camera::CameraPlugin,
     ))
+    .add_plugins((
+      interaction::InteractionPlugin,
+      flags::FlagsPlugin,
+      dialogue::DialoguePlugin,
+      quest::QuestPlugin,
+      save::SavePlugin,
+      editor::EditorPlugin,
+      vitals::VitalsPlugin,
+      hud::HudPlugin,
+      minimap::MinimapPlugin,
+      fog::FogPlugin,
+      lighting::LightingPlugin,
+      clock::ClockPlugin,
+      particles::ParticlesPlugin,
+      audio::GameAudioPlugin,
+    ))
     .run();
 }
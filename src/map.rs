@@ -1,15 +1,30 @@
 use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
 
-use crate::tile::{
-  rect_range_with_x_flip, Direction8, TextureAtlasWithGrid, Tile, TileAtlases,
-  TilePosition, TileSheetCoords, TileType, VerticalPart,
+use crate::{
+  data::RonAssetLoader,
+  dialogue::Dialogue,
+  interaction::{Interactable, InteractionKind},
+  item::{spawn_pickup, ItemStack},
+  loading::LoadingAssets,
+  player::status::PlayerStatus,
+  state::{AppState, Run, RunEntity, StartRun},
+  terrain::{FootstepMaterial, Terrain},
+  tile::{
+    rect_range_with_x_flip, refresh_tile_sprites, Direction8, Tile,
+    TileAtlases, TilePosition, TileSheetCoords, TileType, VerticalPart,
+  },
 };
 
-#[derive(Clone, Reflect)]
-enum MapTile {
+#[derive(Clone, Debug, Default, Reflect, PartialEq, Serialize, Deserialize)]
+pub enum MapTile {
+  #[default]
   Grass,
   FloweryGrass,
   Flagstone,
+  TallGrass,
+  Ice,
+  Lava,
   TallWall {
     corner: Direction8,
     part:   VerticalPart,
@@ -19,9 +34,12 @@ enum MapTile {
 impl TileType for MapTile {
   fn size_and_center(&self) -> (Vec2, Vec2) {
     match self {
-      MapTile::Grass | MapTile::FloweryGrass | MapTile::Flagstone => {
-        (Vec2::splat(16.0), Vec2::ZERO)
-      }
+      MapTile::Grass
+      | MapTile::FloweryGrass
+      | MapTile::Flagstone
+      | MapTile::TallGrass
+      | MapTile::Ice
+      | MapTile::Lava => (Vec2::splat(16.0), Vec2::ZERO),
       MapTile::TallWall { .. } => (Vec2::splat(16.0), Vec2::ZERO),
     }
   }
@@ -30,6 +48,9 @@ impl TileType for MapTile {
       MapTile::Grass => rect_range_with_x_flip(0, 0, 4, 4),
       MapTile::FloweryGrass => rect_range_with_x_flip(4, 0, 4, 4),
       MapTile::Flagstone => rect_range_with_x_flip(0, 4, 2, 3),
+      MapTile::TallGrass => rect_range_with_x_flip(0, 0, 4, 1),
+      MapTile::Ice => rect_range_with_x_flip(0, 1, 4, 1),
+      MapTile::Lava => rect_range_with_x_flip(0, 2, 4, 1),
       MapTile::TallWall { corner, part } => match corner {
         Direction8::North => match part {
           VerticalPart::Top => vec![TileSheetCoords::new(2, 1)],
@@ -66,90 +87,323 @@ impl TileType for MapTile {
       },
     }
   }
-  fn atlas_handle(&self, atlases: &TileAtlases) -> TextureAtlasWithGrid {
+  fn atlas_id(&self) -> &str {
     match self {
-      MapTile::Grass | MapTile::FloweryGrass | MapTile::Flagstone => {
-        atlases.grass.clone()
+      MapTile::Grass | MapTile::FloweryGrass | MapTile::Flagstone => "grass",
+      MapTile::TallGrass | MapTile::Ice | MapTile::Lava => "terrain",
+      MapTile::TallWall { .. } => "wall",
+    }
+  }
+}
+
+impl MapTile {
+  /// Every kind of tile a map can be painted with.
+  pub fn palette() -> Vec<MapTile> {
+    let mut palette = vec![
+      MapTile::Grass,
+      MapTile::FloweryGrass,
+      MapTile::Flagstone,
+      MapTile::TallGrass,
+      MapTile::Ice,
+      MapTile::Lava,
+    ];
+    for part in [VerticalPart::Top, VerticalPart::Bottom] {
+      for corner in Direction8::ALL {
+        palette.push(MapTile::TallWall { corner, part });
       }
-      MapTile::TallWall { .. } => atlases.wall.clone(),
+    }
+    palette
+  }
+
+  /// How the ground moves, hurts and sounds under whoever walks on it.
+  pub fn terrain(&self) -> Terrain {
+    match self {
+      MapTile::Grass | MapTile::FloweryGrass => Terrain::default(),
+      MapTile::Flagstone | MapTile::TallWall { .. } => Terrain {
+        footstep: FootstepMaterial::Stone,
+        ..default()
+      },
+      MapTile::TallGrass => Terrain {
+        speed: 0.6,
+        ..default()
+      },
+      MapTile::Ice => Terrain {
+        slipperiness: 0.9,
+        footstep: FootstepMaterial::Ice,
+        ..default()
+      },
+      MapTile::Lava => Terrain {
+        speed: 0.8,
+        damage_per_second: 2.0,
+        footstep: FootstepMaterial::Stone,
+        ..default()
+      },
     }
   }
 }
 
 impl Tile<MapTile> {
-  #[allow(dead_code)]
-  fn passable(&self) -> bool {
-    match self._type {
-      MapTile::Grass => true,
-      MapTile::FloweryGrass => true,
-      MapTile::Flagstone => true,
-      MapTile::TallWall { .. } => false,
+  pub fn passable(&self) -> bool {
+    !matches!(self._type, MapTile::TallWall { .. })
+  }
+}
+
+/// One tile of a map file.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapCell {
+  pub x:       i64,
+  pub y:       i64,
+  pub layer:   u8,
+  pub tile:    MapTile,
+  #[serde(default)]
+  pub variant: usize,
+}
+
+impl MapCell {
+  pub fn position(&self) -> TilePosition {
+    TilePosition::new(self.x, self.y, self.layer)
+  }
+}
+
+/// A named part of a map, with its own music. Corners are inclusive tile
+/// coordinates.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapArea {
+  pub name:  String,
+  pub music: String,
+  pub min:   (i64, i64),
+  pub max:   (i64, i64),
+}
+
+impl MapArea {
+  pub fn contains(&self, position: Vec2) -> bool {
+    (self.min.0 as f32..=self.max.0 as f32).contains(&position.x)
+      && (self.min.1 as f32..=self.max.1 as f32).contains(&position.y)
+  }
+}
+
+/// A map's tiles, loaded from a `.map.ron` file.
+#[derive(Asset, TypePath, Serialize, Deserialize)]
+pub struct MapData {
+  pub tiles: Vec<MapCell>,
+  /// Where overlapping areas meet, the first listed wins.
+  #[serde(default)]
+  pub areas: Vec<MapArea>,
+}
+
+impl MapData {
+  pub fn area_at(&self, position: Vec2) -> Option<&MapArea> {
+    self.areas.iter().find(|area| area.contains(position))
+  }
+  pub fn get(&self, pos: TilePosition) -> Option<&MapCell> {
+    self.tiles.iter().find(|cell| cell.position() == pos)
+  }
+  /// Puts `cell` at `pos`, or clears it, returning whatever was there.
+  pub fn set(
+    &mut self,
+    pos: TilePosition,
+    cell: Option<MapCell>,
+  ) -> Option<MapCell> {
+    let index = self.tiles.iter().position(|c| c.position() == pos);
+    match (index, cell) {
+      (Some(index), Some(cell)) => {
+        Some(std::mem::replace(&mut self.tiles[index], cell))
+      }
+      (Some(index), None) => Some(self.tiles.remove(index)),
+      (None, Some(cell)) => {
+        self.tiles.push(cell);
+        None
+      }
+      (None, None) => None,
     }
   }
 }
 
+#[derive(Resource)]
+pub struct MapHandle(pub Handle<MapData>);
+
+impl FromWorld for MapHandle {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    MapHandle(asset_server.load("maps/start.map.ron"))
+  }
+}
+
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
-  fn build(&self, app: &mut App) { app.add_systems(Startup, setup); }
-}
-
-fn setup(mut commands: Commands, atlases: Res<TileAtlases>) {
-  let mut map = HashMap::new();
-  for i in -10..=10 {
-    for j in -10..=10 {
-      let pos = TilePosition {
-        x:     i * 2,
-        y:     j * 2,
-        layer: 0,
-      };
-      let _type = if i == 0 && j == 0 {
-        MapTile::Flagstone
-      } else if i % 2 == 0 && j % 2 == 0 {
-        MapTile::FloweryGrass
-      } else {
-        MapTile::Grass
-      };
-      map.insert(pos, Tile {
-        _type,
-        variant: (i + 10) as usize * (j + 10) as usize,
-      });
-    }
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<Tile<MapTile>>()
+      .init_asset::<MapData>()
+      .register_asset_loader(RonAssetLoader::<MapData>::new(&["map.ron"]))
+      .init_resource::<MapHandle>()
+      .add_systems(OnEnter(AppState::Loading), track_map)
+      .add_systems(StartRun, setup)
+      .add_systems(
+        Update,
+        (
+          reload_map.run_if(resource_exists::<Run>()),
+          refresh_tile_sprites::<MapTile>
+            .run_if(resource_exists_and_changed::<TileAtlases>()),
+        ),
+      );
   }
+}
 
-  let mut wall_map = HashMap::new();
-  wall_map.insert((3, 5), Direction8::SouthWest);
-  wall_map.insert((5, 5), Direction8::South);
-  wall_map.insert((7, 5), Direction8::SouthEast);
-  wall_map.insert((3, 7), Direction8::West);
-  wall_map.insert((7, 7), Direction8::East);
-  wall_map.insert((3, 9), Direction8::NorthWest);
-  wall_map.insert((5, 9), Direction8::North);
-  wall_map.insert((7, 9), Direction8::NorthEast);
-
-  for (pos, corner) in wall_map {
-    map.insert(
-      TilePosition::new(pos.0, pos.1, 1),
-      Tile::new(MapTile::TallWall {
-        corner,
-        part: VerticalPart::Bottom,
-      }),
-    );
-    map.insert(
-      TilePosition::new(pos.0, pos.1 + 2, 2),
-      Tile::new(MapTile::TallWall {
-        corner,
-        part: VerticalPart::Top,
-      }),
-    );
-  }
-
-  for (pos, tile) in map {
-    commands.spawn(SpriteSheetBundle {
-      texture_atlas: tile._type.atlas_handle(&atlases).atlas,
+fn track_map(map: Res<MapHandle>, mut loading: ResMut<LoadingAssets>) {
+  loading.add(map.0.clone().untyped());
+}
+
+/// The wall players can read.
+fn is_inscription(tile: &MapTile) -> bool {
+  matches!(tile, MapTile::TallWall {
+    corner: Direction8::South,
+    part:   VerticalPart::Bottom,
+  })
+}
+
+fn spawn_map_tile(
+  commands: &mut Commands,
+  atlases: &TileAtlases,
+  cell: &MapCell,
+) -> Entity {
+  let pos = cell.position();
+  let tile = Tile {
+    _type:   cell.tile.clone(),
+    variant: cell.variant,
+  };
+  let mut entity = commands.spawn((
+    SpriteSheetBundle {
+      texture_atlas: tile._type.atlas_handle(atlases).atlas,
       transform: pos.transform(&tile._type),
-      sprite: tile.texture_atlas_sprite(&atlases),
+      sprite: tile.texture_atlas_sprite(atlases),
       ..Default::default()
-    });
+    },
+    pos,
+    RunEntity,
+  ));
+  if is_inscription(&tile._type) {
+    entity.insert((
+      Name::new("inscription"),
+      Interactable::new(InteractionKind::Read, "Read"),
+    ));
   }
+  entity.insert(tile);
+  entity.id()
+}
+
+/// Brings the spawned map in line with the map file after it changes on disk,
+/// touching only the tiles that differ.
+#[allow(clippy::type_complexity)]
+fn reload_map(
+  mut commands: Commands,
+  mut events: EventReader<AssetEvent<MapData>>,
+  handle: Res<MapHandle>,
+  maps: Res<Assets<MapData>>,
+  atlases: Res<TileAtlases>,
+  mut tiles: Query<(
+    Entity,
+    &TilePosition,
+    &mut Tile<MapTile>,
+    &mut Handle<TextureAtlas>,
+    &mut TextureAtlasSprite,
+  )>,
+) {
+  let modified = events.read().any(|event| event.is_modified(&handle.0));
+  if !modified {
+    return;
+  }
+  let Some(map) = maps.get(&handle.0) else {
+    return;
+  };
+
+  let mut cells = map
+    .tiles
+    .iter()
+    .map(|cell| (cell.position(), cell))
+    .collect::<HashMap<_, _>>();
+  for (entity, pos, mut tile, mut atlas, mut sprite) in tiles.iter_mut() {
+    let Some(cell) = cells.remove(pos) else {
+      commands.entity(entity).despawn_recursive();
+      continue;
+    };
+    if tile._type == cell.tile && tile.variant == cell.variant {
+      continue;
+    }
+    if is_inscription(&tile._type) != is_inscription(&cell.tile) {
+      if is_inscription(&cell.tile) {
+        commands.entity(entity).insert((
+          Name::new("inscription"),
+          Interactable::new(InteractionKind::Read, "Read"),
+        ));
+      } else {
+        commands.entity(entity).remove::<(Name, Interactable)>();
+      }
+    }
+    *tile = Tile {
+      _type:   cell.tile.clone(),
+      variant: cell.variant,
+    };
+    *atlas = tile._type.atlas_handle(&atlases).atlas;
+    *sprite = tile.texture_atlas_sprite(&atlases);
+  }
+  for cell in cells.values() {
+    spawn_map_tile(&mut commands, &atlases, cell);
+  }
+}
+
+fn setup(
+  mut commands: Commands,
+  atlases: Res<TileAtlases>,
+  asset_server: Res<AssetServer>,
+  handle: Res<MapHandle>,
+  maps: Res<Assets<MapData>>,
+) {
+  match maps.get(&handle.0) {
+    Some(map) => {
+      for cell in &map.tiles {
+        spawn_map_tile(&mut commands, &atlases, cell);
+      }
+    }
+    None => error!("starting a run before the map has loaded"),
+  }
+
+  spawn_pickup(
+    &mut commands,
+    &atlases,
+    ItemStack::new("apple", 3),
+    TilePosition::new(-4, -4, 1),
+  );
+  spawn_pickup(
+    &mut commands,
+    &atlases,
+    ItemStack::new("coin", 12),
+    TilePosition::new(4, -4, 1),
+  );
+  spawn_pickup(
+    &mut commands,
+    &atlases,
+    ItemStack::new("silver_ring", 1),
+    TilePosition::new(16, 14, 1),
+  );
+
+  let elder = Tile::new(PlayerStatus::Stand(Direction8::South));
+  let elder_pos = TilePosition::new(-6, 4, 1);
+  commands.spawn((
+    SpriteSheetBundle {
+      texture_atlas: elder._type.atlas_handle(&atlases).atlas,
+      transform: elder_pos.transform(&elder._type),
+      sprite: TextureAtlasSprite {
+        color: Color::rgb(0.8, 0.75, 0.7),
+        ..elder.texture_atlas_sprite(&atlases)
+      },
+      ..Default::default()
+    },
+    Name::new("elder"),
+    Interactable::new(InteractionKind::Talk, "Talk"),
+    Dialogue(asset_server.load("dialogue/elder.dialogue.ron")),
+    elder,
+    RunEntity,
+  ));
 }
@@ -0,0 +1,304 @@
+use bevy::{
+  prelude::*,
+  render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+  utils::HashMap,
+};
+
+use crate::{
+  hud::HudScale,
+  map::MapTile,
+  player::{Player, PlayerId},
+  state::{GameplaySet, RunEntity, StartRun},
+  tile::{Tile, TilePosition},
+};
+
+/// The width and height of the minimap texture, in map cells. Cells further
+/// than half of this from the origin aren't drawn.
+const MAP_CELLS: u32 = 128;
+/// How many cells across the on-screen view shows.
+const VIEW_CELLS: f32 = 48.0;
+/// The size of one cell on screen, in art pixels.
+const CELL_PIXELS: f32 = 2.0;
+/// The size of a marker dot, in art pixels.
+const MARKER_PIXELS: f32 = 3.0;
+
+/// Shows this entity on the minimap as a dot of the given colour.
+#[derive(Component, Clone, Copy)]
+pub struct MinimapMarker(pub Color);
+
+/// The minimap texture, with enough bookkeeping to repaint single cells when
+/// the tiles on them change.
+#[derive(Resource)]
+pub struct Minimap {
+  pub image: Handle<Image>,
+  /// The pixel, layer and colour each map tile contributes.
+  tiles:     HashMap<Entity, (UVec2, u8, Color)>,
+  /// The tiles drawn on each pixel.
+  pixels:    HashMap<UVec2, Vec<Entity>>,
+}
+
+impl FromWorld for Minimap {
+  fn from_world(world: &mut World) -> Self {
+    let image = Image::new_fill(
+      Extent3d {
+        width:                 MAP_CELLS,
+        height:                MAP_CELLS,
+        depth_or_array_layers: 1,
+      },
+      TextureDimension::D2,
+      &[0, 0, 0, 0],
+      TextureFormat::Rgba8UnormSrgb,
+    );
+    let mut images = world.get_resource_mut::<Assets<Image>>().unwrap();
+    Minimap {
+      image:  images.add(image),
+      tiles:  HashMap::new(),
+      pixels: HashMap::new(),
+    }
+  }
+}
+
+impl Minimap {
+  fn remove(&mut self, entity: Entity) -> Option<UVec2> {
+    let (pixel, ..) = self.tiles.remove(&entity)?;
+    if let Some(entities) = self.pixels.get_mut(&pixel) {
+      entities.retain(|e| *e != entity);
+    }
+    Some(pixel)
+  }
+
+  fn insert(&mut self, entity: Entity, pixel: UVec2, layer: u8, color: Color) {
+    self.tiles.insert(entity, (pixel, layer, color));
+    self.pixels.entry(pixel).or_default().push(entity);
+  }
+
+  /// The colour of the topmost tile on a pixel.
+  fn color(&self, pixel: UVec2) -> Color {
+    self
+      .pixels
+      .get(&pixel)
+      .into_iter()
+      .flatten()
+      .filter_map(|entity| self.tiles.get(entity))
+      .max_by_key(|(_, layer, _)| *layer)
+      .map_or(Color::NONE, |(.., color)| *color)
+  }
+}
+
+fn tile_color(tile: &MapTile) -> Color {
+  match tile {
+    MapTile::Grass => Color::rgb(0.2, 0.45, 0.2),
+    MapTile::FloweryGrass => Color::rgb(0.3, 0.55, 0.25),
+    MapTile::Flagstone => Color::rgb(0.55, 0.55, 0.5),
+    MapTile::TallGrass => Color::rgb(0.15, 0.35, 0.1),
+    MapTile::Ice => Color::rgb(0.7, 0.85, 0.95),
+    MapTile::Lava => Color::rgb(0.9, 0.35, 0.05),
+    MapTile::TallWall { .. } => Color::rgb(0.95, 0.9, 0.75),
+  }
+}
+
+/// The continuous position on the minimap texture of a world position, in
+/// pixels from its top-left corner.
+fn texture_position(world: Vec2) -> Vec2 {
+  let cell = (world + Vec2::ONE) / 2.0;
+  let half = (MAP_CELLS / 2) as f32;
+  Vec2::new(half + cell.x, half - cell.y)
+}
+
+/// The texture pixel a tile is drawn on, if it's in range.
+fn pixel(pos: &TilePosition) -> Option<UVec2> {
+  let half = (MAP_CELLS / 2) as i64;
+  let x = pos.x.div_euclid(2) + half;
+  let y = half - 1 - pos.y.div_euclid(2);
+  let range = 0..MAP_CELLS as i64;
+  (range.contains(&x) && range.contains(&y))
+    .then(|| UVec2::new(x as u32, y as u32))
+}
+
+/// Repaints only the pixels whose tiles were added, changed or removed.
+#[allow(clippy::type_complexity)]
+fn track_map_tiles(
+  tiles: Query<(Entity, &TilePosition, &Tile<MapTile>), Changed<Tile<MapTile>>>,
+  mut removed: RemovedComponents<Tile<MapTile>>,
+  mut minimap: ResMut<Minimap>,
+  mut images: ResMut<Assets<Image>>,
+) {
+  let mut dirty = Vec::new();
+  for entity in removed.read() {
+    dirty.extend(minimap.remove(entity));
+  }
+  for (entity, pos, tile) in tiles.iter() {
+    dirty.extend(minimap.remove(entity));
+    let Some(pixel) = pixel(pos) else {
+      continue;
+    };
+    minimap.insert(entity, pixel, pos.layer, tile_color(&tile._type));
+    dirty.push(pixel);
+  }
+  if dirty.is_empty() {
+    return;
+  }
+
+  let Some(image) = images.get_mut(&minimap.image) else {
+    return;
+  };
+  for pixel in dirty {
+    let index = ((pixel.y * MAP_CELLS + pixel.x) * 4) as usize;
+    image.data[index..index + 4]
+      .copy_from_slice(&minimap.color(pixel).as_rgba_u8());
+  }
+}
+
+#[derive(Component)]
+struct MinimapView;
+
+#[derive(Component)]
+struct MinimapImage;
+
+#[derive(Component)]
+struct MinimapDot(Entity);
+
+fn spawn_minimap(mut commands: Commands, minimap: Res<Minimap>) {
+  commands
+    .spawn((
+      NodeBundle {
+        style: Style {
+          position_type: PositionType::Absolute,
+          right: Val::Px(8.0),
+          bottom: Val::Px(8.0),
+          overflow: Overflow::clip(),
+          ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..default()
+      },
+      Name::new("minimap"),
+      MinimapView,
+      RunEntity,
+    ))
+    .with_children(|view| {
+      view.spawn((
+        ImageBundle {
+          style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+          },
+          image: UiImage::new(minimap.image.clone()),
+          ..default()
+        },
+        MinimapImage,
+      ));
+    });
+}
+
+fn mark_players(
+  mut commands: Commands,
+  players: Query<Entity, (With<Player>, Without<MinimapMarker>)>,
+) {
+  for player in players.iter() {
+    commands.entity(player).insert(MinimapMarker(Color::WHITE));
+  }
+}
+
+/// Keeps one dot per marked entity in the view.
+fn sync_minimap_dots(
+  mut commands: Commands,
+  views: Query<Entity, With<MinimapView>>,
+  markers: Query<(Entity, &MinimapMarker)>,
+  dots: Query<(Entity, &MinimapDot)>,
+) {
+  let Ok(view) = views.get_single() else {
+    return;
+  };
+  for (dot, MinimapDot(target)) in dots.iter() {
+    if markers.get(*target).is_err() {
+      commands.entity(dot).despawn_recursive();
+    }
+  }
+  for (entity, marker) in markers.iter() {
+    if dots.iter().any(|(_, dot)| dot.0 == entity) {
+      continue;
+    }
+    commands.entity(view).with_children(|view| {
+      view.spawn((
+        NodeBundle {
+          style: Style {
+            position_type: PositionType::Absolute,
+            ..default()
+          },
+          background_color: marker.0.into(),
+          ..default()
+        },
+        MinimapDot(entity),
+      ));
+    });
+  }
+}
+
+/// Centres the view on the first player and places the dots around them.
+#[allow(clippy::type_complexity)]
+fn follow_player(
+  players: Query<(&PlayerId, &GlobalTransform)>,
+  targets: Query<&GlobalTransform>,
+  mut views: Query<&mut Style, With<MinimapView>>,
+  mut images: Query<&mut Style, (With<MinimapImage>, Without<MinimapView>)>,
+  mut dots: Query<
+    (&MinimapDot, &mut Style),
+    (Without<MinimapView>, Without<MinimapImage>),
+  >,
+  scale: Res<HudScale>,
+) {
+  let Some((_, leader)) = players.iter().min_by_key(|(id, _)| **id) else {
+    return;
+  };
+  let center = texture_position(leader.translation().xy());
+  let cell = CELL_PIXELS * scale.0;
+  let size = VIEW_CELLS * cell;
+
+  for mut style in views.iter_mut() {
+    style.width = Val::Px(size);
+    style.height = Val::Px(size);
+  }
+  for mut style in images.iter_mut() {
+    let offset = Vec2::splat(size / 2.0) - center * cell;
+    style.width = Val::Px(MAP_CELLS as f32 * cell);
+    style.height = Val::Px(MAP_CELLS as f32 * cell);
+    style.left = Val::Px(offset.x);
+    style.top = Val::Px(offset.y);
+  }
+  let dot = MARKER_PIXELS * scale.0;
+  for (MinimapDot(target), mut style) in dots.iter_mut() {
+    let Ok(transform) = targets.get(*target) else {
+      continue;
+    };
+    let position = (texture_position(transform.translation().xy()) - center)
+      * cell
+      + Vec2::splat((size - dot) / 2.0);
+    style.width = Val::Px(dot);
+    style.height = Val::Px(dot);
+    style.left = Val::Px(position.x);
+    style.top = Val::Px(position.y);
+  }
+}
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<Minimap>()
+      .add_systems(StartRun, spawn_minimap)
+      .add_systems(Update, track_map_tiles)
+      .add_systems(
+        Update,
+        (
+          mark_players,
+          sync_minimap_dots,
+          apply_deferred,
+          follow_player,
+        )
+          .chain()
+          .in_set(GameplaySet),
+      );
+  }
+}
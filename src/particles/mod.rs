@@ -0,0 +1,355 @@
+mod weather;
+
+use bevy::prelude::*;
+
+pub use self::weather::{Weather, WeatherKind};
+use crate::{
+  player::{status::PlayerStatus, Player},
+  state::{EndRun, GameplaySet},
+  vitals::Damage,
+};
+
+/// The most particles alive at once. Requests past this are dropped.
+const MAX_PARTICLES: usize = 2000;
+/// How far above the sprites particles are drawn, below the light map.
+const PARTICLE_Z: f32 = 50.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum ParticleKind {
+  Rain,
+  Snow,
+  Leaf,
+  #[default]
+  Dust,
+  Spark,
+}
+
+fn random_range(min: f32, max: f32) -> f32 {
+  min + fastrand::f32() * (max - min)
+}
+
+impl ParticleKind {
+  /// A new particle of this kind, with a little randomness.
+  fn particle(&self) -> Particle {
+    let direction = Vec2::from_angle(random_range(0.0, std::f32::consts::TAU));
+    match self {
+      ParticleKind::Rain => Particle {
+        velocity: Vec2::new(-3.0, -28.0),
+        size: Vec2::new(0.06, 0.6),
+        color: Color::rgba(0.6, 0.7, 0.9, 0.6),
+        lifetime: random_range(0.5, 0.8),
+        ..default()
+      },
+      ParticleKind::Snow => Particle {
+        velocity: Vec2::new(random_range(-0.5, 0.5), -2.5),
+        size: Vec2::splat(0.15),
+        color: Color::rgba(1.0, 1.0, 1.0, 0.9),
+        lifetime: random_range(4.0, 6.0),
+        sway: 1.0,
+        ..default()
+      },
+      ParticleKind::Leaf => Particle {
+        velocity: Vec2::new(random_range(1.0, 2.5), -1.5),
+        size: Vec2::new(0.25, 0.15),
+        color: if fastrand::bool() {
+          Color::rgb(0.8, 0.45, 0.1)
+        } else {
+          Color::rgb(0.5, 0.6, 0.15)
+        },
+        lifetime: random_range(5.0, 8.0),
+        sway: 1.5,
+        spin: random_range(-3.0, 3.0),
+        ..default()
+      },
+      ParticleKind::Dust => Particle {
+        velocity: Vec2::new(direction.x * 0.8, random_range(0.3, 1.0)),
+        size: Vec2::splat(random_range(0.2, 0.35)),
+        color: Color::rgba(0.7, 0.6, 0.45, 0.5),
+        lifetime: random_range(0.3, 0.6),
+        ..default()
+      },
+      ParticleKind::Spark => Particle {
+        velocity: direction * random_range(4.0, 9.0),
+        acceleration: Vec2::new(0.0, -20.0),
+        size: Vec2::splat(0.12),
+        color: Color::rgb(1.0, 0.9, 0.4),
+        lifetime: random_range(0.2, 0.4),
+        ..default()
+      },
+    }
+  }
+}
+
+/// A pooled particle. Dead particles are hidden and wait in the pool.
+#[derive(Component, Clone, Debug, Default)]
+struct Particle {
+  velocity:     Vec2,
+  acceleration: Vec2,
+  size:         Vec2,
+  color:        Color,
+  age:          f32,
+  lifetime:     f32,
+  /// How far the particle drifts from side to side, in units per second.
+  sway:         f32,
+  /// Radians per second.
+  spin:         f32,
+}
+
+/// The particle entities, and which of them are free to reuse.
+#[derive(Resource, Default)]
+struct ParticlePool {
+  free:  Vec<Entity>,
+  total: usize,
+}
+
+/// Asks for `count` particles at random positions in `area`.
+#[derive(Event, Clone, Debug)]
+pub struct EmitParticles {
+  pub kind:  ParticleKind,
+  pub area:  Rect,
+  pub count: u32,
+}
+
+impl EmitParticles {
+  pub fn at(kind: ParticleKind, position: Vec2, count: u32) -> Self {
+    EmitParticles {
+      kind,
+      area: Rect::from_center_size(position, Vec2::ZERO),
+      count,
+    }
+  }
+}
+
+/// Emits particles from an entity while enabled.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct ParticleEmitter {
+  pub kind:    ParticleKind,
+  /// Particles per second.
+  pub rate:    f32,
+  /// Where particles come from, relative to the entity.
+  pub offset:  Vec2,
+  pub enabled: bool,
+  /// Fractions of a particle owed from earlier frames.
+  owed:        f32,
+}
+
+impl ParticleEmitter {
+  pub fn new(kind: ParticleKind, rate: f32) -> Self {
+    ParticleEmitter {
+      kind,
+      rate,
+      offset: Vec2::ZERO,
+      enabled: true,
+      owed: 0.0,
+    }
+  }
+  pub fn with_offset(mut self, offset: Vec2) -> Self {
+    self.offset = offset;
+    self
+  }
+}
+
+fn spawn_particles(
+  mut commands: Commands,
+  mut events: EventReader<EmitParticles>,
+  mut pool: ResMut<ParticlePool>,
+  mut particles: Query<(
+    &mut Particle,
+    &mut Transform,
+    &mut Sprite,
+    &mut Visibility,
+  )>,
+) {
+  for event in events.read() {
+    for _ in 0..event.count {
+      let particle = event.kind.particle();
+      let position = Vec2::new(
+        random_range(event.area.min.x, event.area.max.x),
+        random_range(event.area.min.y, event.area.max.y),
+      );
+      let transform = Transform::from_translation(position.extend(PARTICLE_Z));
+
+      if let Some(entity) = pool.free.pop() {
+        if let Ok((
+          mut current,
+          mut current_transform,
+          mut sprite,
+          mut visibility,
+        )) = particles.get_mut(entity)
+        {
+          sprite.color = particle.color;
+          sprite.custom_size = Some(particle.size);
+          *current = particle;
+          *current_transform = transform;
+          *visibility = Visibility::Inherited;
+          continue;
+        }
+      }
+      if pool.total >= MAX_PARTICLES {
+        break;
+      }
+      pool.total += 1;
+      commands.spawn((
+        SpriteBundle {
+          sprite: Sprite {
+            color: particle.color,
+            custom_size: Some(particle.size),
+            ..default()
+          },
+          transform,
+          ..default()
+        },
+        Name::new("particle"),
+        particle,
+      ));
+    }
+  }
+}
+
+fn update_particles(
+  mut pool: ResMut<ParticlePool>,
+  mut particles: Query<(
+    Entity,
+    &mut Particle,
+    &mut Transform,
+    &mut Sprite,
+    &mut Visibility,
+  )>,
+  time: Res<Time>,
+) {
+  let delta = time.delta_seconds();
+  for (entity, mut particle, mut transform, mut sprite, mut visibility) in
+    particles.iter_mut()
+  {
+    if *visibility == Visibility::Hidden {
+      continue;
+    }
+    particle.age += delta;
+    if particle.age >= particle.lifetime {
+      *visibility = Visibility::Hidden;
+      pool.free.push(entity);
+      continue;
+    }
+
+    let acceleration = particle.acceleration;
+    particle.velocity += acceleration * delta;
+    let sway = (particle.age * 3.0).sin() * particle.sway;
+    transform.translation +=
+      (particle.velocity + Vec2::new(sway, 0.0)).extend(0.0) * delta;
+    transform.rotate_z(particle.spin * delta);
+
+    let fade = 1.0 - particle.age / particle.lifetime;
+    sprite.custom_size = Some(particle.size);
+    sprite.color = particle.color.with_a(particle.color.a() * fade);
+  }
+}
+
+fn run_emitters(
+  mut emitters: Query<(&mut ParticleEmitter, &GlobalTransform)>,
+  mut events: EventWriter<EmitParticles>,
+  time: Res<Time>,
+) {
+  for (mut emitter, transform) in emitters.iter_mut() {
+    if !emitter.enabled {
+      emitter.owed = 0.0;
+      continue;
+    }
+    emitter.owed += emitter.rate * time.delta_seconds();
+    let count = emitter.owed.floor();
+    if count < 1.0 {
+      continue;
+    }
+    emitter.owed -= count;
+    let position = transform.translation().xy() + emitter.offset;
+    events.send(EmitParticles::at(emitter.kind, position, count as u32));
+  }
+}
+
+/// Kicks up dust under running players.
+fn player_dust(
+  mut commands: Commands,
+  mut players: Query<(Entity, &Player, Option<&mut ParticleEmitter>)>,
+) {
+  for (entity, player, emitter) in players.iter_mut() {
+    let running = matches!(player.0, PlayerStatus::Run(_));
+    match emitter {
+      Some(mut emitter) => {
+        if emitter.enabled != running {
+          emitter.enabled = running;
+        }
+      }
+      None => {
+        // at the player's feet
+        let emitter = ParticleEmitter::new(ParticleKind::Dust, 24.0)
+          .with_offset(Vec2::new(0.0, -1.4));
+        commands.entity(entity).insert(ParticleEmitter {
+          enabled: running,
+          ..emitter
+        });
+      }
+    }
+  }
+}
+
+fn hit_sparks(
+  mut damage: EventReader<Damage>,
+  targets: Query<&GlobalTransform>,
+  mut events: EventWriter<EmitParticles>,
+) {
+  for event in damage.read() {
+    if event.amount <= 0.0 {
+      continue;
+    }
+    let Ok(transform) = targets.get(event.entity) else {
+      continue;
+    };
+    events.send(EmitParticles::at(
+      ParticleKind::Spark,
+      transform.translation().xy(),
+      12,
+    ));
+  }
+}
+
+/// Puts every particle back in the pool.
+fn clear_particles(
+  mut pool: ResMut<ParticlePool>,
+  mut particles: Query<(Entity, &mut Visibility), With<Particle>>,
+) {
+  pool.free.clear();
+  for (entity, mut visibility) in particles.iter_mut() {
+    *visibility = Visibility::Hidden;
+    pool.free.push(entity);
+  }
+}
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<ParticleEmitter>()
+      .register_type::<Weather>()
+      .init_resource::<ParticlePool>()
+      .init_resource::<Weather>()
+      .add_event::<EmitParticles>()
+      .add_systems(
+        Update,
+        (
+          (
+            player_dust,
+            weather::update_weather,
+            weather::change_weather_at_dawn,
+          ),
+          (run_emitters, hit_sparks, weather::emit_weather),
+          update_particles,
+          spawn_particles,
+        )
+          .chain()
+          .after(crate::player::apply_movement)
+          .in_set(GameplaySet),
+      )
+      .add_systems(EndRun, (clear_particles, weather::reset_weather));
+  }
+}
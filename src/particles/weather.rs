@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+
+use super::{random_range, EmitParticles, ParticleKind};
+use crate::{camera::MainCamera, clock::DayEvent};
+
+/// How long the weather takes to change at dawn, in seconds.
+const DAWN_TRANSITION: f32 = 20.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum WeatherKind {
+  #[default]
+  Clear,
+  Rain,
+  Snow,
+  Leaves,
+}
+
+impl WeatherKind {
+  pub const ALL: [WeatherKind; 4] = [
+    WeatherKind::Clear,
+    WeatherKind::Rain,
+    WeatherKind::Snow,
+    WeatherKind::Leaves,
+  ];
+
+  /// The particles this weather brings, and how many fall per second on each
+  /// square unit of the view.
+  fn particles(&self) -> Option<(ParticleKind, f32)> {
+    match self {
+      WeatherKind::Clear => None,
+      WeatherKind::Rain => Some((ParticleKind::Rain, 1.2)),
+      WeatherKind::Snow => Some((ParticleKind::Snow, 0.25)),
+      WeatherKind::Leaves => Some((ParticleKind::Leaf, 0.05)),
+    }
+  }
+}
+
+/// The weather, fading from `current` to `next` while they differ.
+#[derive(Resource, Clone, Debug, Default, Reflect)]
+#[reflect(Resource)]
+pub struct Weather {
+  pub current:  WeatherKind,
+  pub next:     WeatherKind,
+  /// How far the change to `next` has got, from 0.0 to 1.0.
+  pub progress: f32,
+  /// How long the change takes, in seconds.
+  pub duration: f32,
+}
+
+impl Weather {
+  /// Starts changing to `kind` over `duration` seconds. A change back to the
+  /// weather being faded out turns around where it is; any other change
+  /// already under way is cut short, keeping whichever weather was showing
+  /// most.
+  pub fn transition_to(&mut self, kind: WeatherKind, duration: f32) {
+    if kind == self.next {
+      self.duration = duration;
+      return;
+    }
+    if kind == self.current {
+      self.current = self.next;
+      self.next = kind;
+      self.progress = 1.0 - self.progress;
+      self.duration = duration;
+      return;
+    }
+    if self.progress >= 0.5 {
+      self.current = self.next;
+    }
+    self.next = kind;
+    self.progress = 0.0;
+    self.duration = duration;
+  }
+
+  /// How strongly `kind` is showing, from 0.0 to 1.0.
+  pub fn intensity(&self, kind: WeatherKind) -> f32 {
+    let mut intensity = 0.0;
+    if self.current == kind {
+      intensity += 1.0 - self.progress;
+    }
+    if self.next == kind {
+      intensity += self.progress;
+    }
+    intensity
+  }
+}
+
+pub(super) fn update_weather(mut weather: ResMut<Weather>, time: Res<Time>) {
+  if weather.current == weather.next {
+    return;
+  }
+  weather.progress += time.delta_seconds() / weather.duration.max(f32::EPSILON);
+  if weather.progress >= 1.0 {
+    weather.current = weather.next;
+    weather.progress = 0.0;
+  }
+}
+
+pub(super) fn change_weather_at_dawn(
+  mut days: EventReader<DayEvent>,
+  mut weather: ResMut<Weather>,
+) {
+  for event in days.read() {
+    if let DayEvent::Dawn { .. } = event {
+      let kind = WeatherKind::ALL[fastrand::usize(..WeatherKind::ALL.len())];
+      weather.transition_to(kind, DAWN_TRANSITION);
+    }
+  }
+}
+
+/// Drops weather particles over every camera's view, and a little above it so
+/// they fall into frame.
+pub(super) fn emit_weather(
+  weather: Res<Weather>,
+  cameras: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+  mut events: EventWriter<EmitParticles>,
+  time: Res<Time>,
+) {
+  for kind in WeatherKind::ALL {
+    let Some((particle, rate)) = kind.particles() else {
+      continue;
+    };
+    let intensity = weather.intensity(kind);
+    if intensity <= 0.0 {
+      continue;
+    }
+    for (transform, projection) in cameras.iter() {
+      let center = transform.translation().xy();
+      let area = Rect {
+        min: center + projection.area.min - Vec2::new(2.0, 0.0),
+        max: center + projection.area.max + Vec2::new(2.0, 8.0),
+      };
+      let size = area.size();
+      let expected = rate * intensity * size.x * size.y * time.delta_seconds();
+      // round randomly so low rates still emit now and then
+      let count = (expected + random_range(0.0, 1.0)).floor() as u32;
+      if count > 0 {
+        events.send(EmitParticles {
+          kind: particle,
+          area,
+          count,
+        });
+      }
+    }
+  }
+}
+
+pub(super) fn reset_weather(mut weather: ResMut<Weather>) {
+  *weather = Weather::default();
+}
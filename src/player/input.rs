@@ -0,0 +1,166 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+/// The input device a local player is bound to.
+#[derive(Component, Clone, Reflect)]
+#[reflect(Component)]
+pub enum PlayerInput {
+  Keyboard(KeyboardBindings),
+  Gamepad(Gamepad),
+}
+
+impl Default for PlayerInput {
+  fn default() -> Self { PlayerInput::Keyboard(KeyboardBindings::wasd()) }
+}
+
+#[derive(Clone, Reflect)]
+pub struct KeyboardBindings {
+  pub up:        KeyCode,
+  pub down:      KeyCode,
+  pub left:      KeyCode,
+  pub right:     KeyCode,
+  pub run:       KeyCode,
+  pub interact:  KeyCode,
+  pub next_slot: KeyCode,
+  pub use_item:  KeyCode,
+}
+
+impl KeyboardBindings {
+  pub fn wasd() -> Self {
+    Self {
+      up:        KeyCode::W,
+      down:      KeyCode::S,
+      left:      KeyCode::A,
+      right:     KeyCode::D,
+      run:       KeyCode::ShiftLeft,
+      interact:  KeyCode::E,
+      next_slot: KeyCode::Q,
+      use_item:  KeyCode::F,
+    }
+  }
+  pub fn arrows() -> Self {
+    Self {
+      up:        KeyCode::Up,
+      down:      KeyCode::Down,
+      left:      KeyCode::Left,
+      right:     KeyCode::Right,
+      run:       KeyCode::ShiftRight,
+      interact:  KeyCode::Return,
+      next_slot: KeyCode::Period,
+      use_item:  KeyCode::Slash,
+    }
+  }
+}
+
+/// Every input source a player can be bound to.
+#[derive(SystemParam)]
+pub struct InputSources<'w> {
+  keys:            Res<'w, Input<KeyCode>>,
+  gamepad_axes:    Res<'w, Axis<GamepadAxis>>,
+  gamepad_buttons: Res<'w, Input<GamepadButton>>,
+}
+
+impl InputSources<'_> {
+  /// The normalized movement direction requested by the given binding.
+  pub fn movement(&self, input: &PlayerInput) -> Vec2 {
+    let mut movement = Vec2::ZERO;
+    match input {
+      PlayerInput::Keyboard(bindings) => {
+        if self.keys.pressed(bindings.up) {
+          movement.y += 1.0;
+        }
+        if self.keys.pressed(bindings.down) {
+          movement.y -= 1.0;
+        }
+        if self.keys.pressed(bindings.right) {
+          movement.x += 1.0;
+        }
+        if self.keys.pressed(bindings.left) {
+          movement.x -= 1.0;
+        }
+      }
+      PlayerInput::Gamepad(gamepad) => {
+        let axis = |axis_type| {
+          self
+            .gamepad_axes
+            .get(GamepadAxis::new(*gamepad, axis_type))
+            .unwrap_or(0.0)
+        };
+        movement.x = axis(GamepadAxisType::LeftStickX);
+        movement.y = axis(GamepadAxisType::LeftStickY);
+      }
+    }
+    movement.normalize_or_zero()
+  }
+  /// Whether the run modifier is held on the given binding.
+  pub fn run(&self, input: &PlayerInput) -> bool {
+    match input {
+      PlayerInput::Keyboard(bindings) => self.keys.pressed(bindings.run),
+      PlayerInput::Gamepad(gamepad) => self.gamepad_buttons.pressed(
+        GamepadButton::new(*gamepad, GamepadButtonType::RightTrigger2),
+      ),
+    }
+  }
+  /// Steps through a menu: -1 when up was pressed this frame, 1 for down.
+  pub fn menu_step(&self, input: &PlayerInput) -> i32 {
+    let (up, down) = match input {
+      PlayerInput::Keyboard(bindings) => (
+        self.keys.just_pressed(bindings.up),
+        self.keys.just_pressed(bindings.down),
+      ),
+      PlayerInput::Gamepad(gamepad) => {
+        let button = |button_type| GamepadButton::new(*gamepad, button_type);
+        (
+          self
+            .gamepad_buttons
+            .just_pressed(button(GamepadButtonType::DPadUp)),
+          self
+            .gamepad_buttons
+            .just_pressed(button(GamepadButtonType::DPadDown)),
+        )
+      }
+    };
+    down as i32 - up as i32
+  }
+  /// Whether the interact action was pressed this frame on the given binding.
+  pub fn interact(&self, input: &PlayerInput) -> bool {
+    match input {
+      PlayerInput::Keyboard(bindings) => {
+        self.keys.just_pressed(bindings.interact)
+      }
+      PlayerInput::Gamepad(gamepad) => self
+        .gamepad_buttons
+        .just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::South)),
+    }
+  }
+  /// Whether the next hotbar slot was asked for this frame.
+  pub fn next_slot(&self, input: &PlayerInput) -> bool {
+    match input {
+      PlayerInput::Keyboard(bindings) => {
+        self.keys.just_pressed(bindings.next_slot)
+      }
+      PlayerInput::Gamepad(gamepad) => self.gamepad_buttons.just_pressed(
+        GamepadButton::new(*gamepad, GamepadButtonType::RightTrigger),
+      ),
+    }
+  }
+  /// Whether the selected hotbar item was used this frame.
+  pub fn use_item(&self, input: &PlayerInput) -> bool {
+    match input {
+      PlayerInput::Keyboard(bindings) => {
+        self.keys.just_pressed(bindings.use_item)
+      }
+      PlayerInput::Gamepad(gamepad) => self
+        .gamepad_buttons
+        .just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::West)),
+    }
+  }
+}
+
+/// The devices that should each get a player when a run starts.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct LocalPlayers(pub Vec<PlayerInput>);
+
+impl Default for LocalPlayers {
+  fn default() -> Self { LocalPlayers(vec![PlayerInput::default()]) }
+}
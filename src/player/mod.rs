@@ -1,31 +1,42 @@
-use bevy::prelude::*;
+use bevy::{input::gamepad::GamepadConnectionEvent, prelude::*};
 
-use self::status::PlayerStatus;
-use crate::tile::{
-  AnimatedTile, Direction4, Tile, TileAtlases, TilePosition, TileType,
+use self::{
+  input::{InputSources, LocalPlayers, PlayerInput},
+  movement::{steer, PlayerSpeeds, Velocity},
+  paperdoll::{PaperdollLayer, PaperdollSheets, SetPaperdollLayer},
+  status::PlayerStatus,
+};
+use crate::{
+  dialogue::dialogue_open,
+  editor::editor_open,
+  interaction::InteractionFocus,
+  item::{Equipment, HotbarSelection, Inventory},
+  state::{GameplaySet, Run, RunEntity, StartRun},
+  stats::{Stats, StatsBundle},
+  terrain::Ground,
+  tile::{
+    refresh_animated_tile_sprites, refresh_tile_sprites, AnimatedTile,
+    AnimationComplete, AnimationEvent, AnimationProgress, Direction8, Tile,
+    TileAtlases, TilePosition, TileType,
+  },
+  vitals::{Health, Stamina},
 };
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
 pub struct Player(pub PlayerStatus);
 
-mod status;
+/// The local player number, starting at zero. Used to order cameras.
+#[derive(
+  Component, Clone, Copy, Reflect, Default, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[reflect(Component)]
+pub struct PlayerId(pub usize);
 
-#[derive(Resource, Reflect)]
-#[reflect(Resource)]
-pub struct PlayerSpeeds {
-  pub walk: f32,
-  pub run:  f32,
-}
-
-impl Default for PlayerSpeeds {
-  fn default() -> Self {
-    PlayerSpeeds {
-      walk: 3.0,
-      run:  4.0,
-    }
-  }
-}
+pub mod input;
+pub mod movement;
+pub mod paperdoll;
+pub mod status;
 
 pub struct PlayerPlugin;
 
@@ -33,46 +44,136 @@ impl Plugin for PlayerPlugin {
   fn build(&self, app: &mut App) {
     app
       .register_type::<Player>()
+      .register_type::<PlayerId>()
+      .register_type::<PlayerInput>()
+      .register_type::<LocalPlayers>()
+      .register_type::<PlayerSpeeds>()
+      .register_type::<Velocity>()
+      .register_type::<PaperdollLayer>()
       .register_type::<Tile<PlayerStatus>>()
       .register_type::<AnimatedTile<PlayerStatus>>()
-      .register_type::<PlayerSpeeds>()
+      .init_resource::<LocalPlayers>()
       .init_resource::<PlayerSpeeds>()
-      .add_systems(Startup, setup)
+      .init_resource::<PaperdollSheets>()
+      .add_event::<SetPaperdollLayer>()
+      .add_systems(StartRun, setup)
       .add_systems(
         Update,
         (
-          accept_movement_input,
-          (apply_movement, update_player_sprite),
+          join_gamepad_players,
+          (
+            accept_movement_input
+              .run_if(not(dialogue_open))
+              .run_if(not(editor_open)),
+            hold_players.run_if(dialogue_open.or_else(editor_open)),
+            (apply_movement, update_player_sprite),
+          )
+            .in_set(GameplaySet),
         )
           .chain(),
+      )
+      .add_systems(
+        Update,
+        (
+          paperdoll::apply_paperdoll_layers,
+          paperdoll::tint_paperdoll_layers,
+          paperdoll::sync_paperdoll_layers,
+        )
+          .chain()
+          .after(update_player_sprite)
+          .in_set(GameplaySet),
+      )
+      .add_systems(
+        Update,
+        (
+          refresh_tile_sprites::<PlayerStatus>,
+          refresh_animated_tile_sprites::<PlayerStatus>,
+        )
+          .run_if(resource_exists_and_changed::<TileAtlases>()),
       );
   }
 }
 
-fn setup(mut commands: Commands, atlases: Res<TileAtlases>) {
-  let status = PlayerStatus::Stand(Direction4::South);
+fn setup(
+  mut commands: Commands,
+  atlases: Res<TileAtlases>,
+  local_players: Res<LocalPlayers>,
+) {
+  for (id, input) in local_players.0.iter().enumerate() {
+    spawn_player(&mut commands, &atlases, PlayerId(id), input.clone());
+  }
+}
+
+pub fn spawn_player(
+  commands: &mut Commands,
+  atlases: &TileAtlases,
+  id: PlayerId,
+  input: PlayerInput,
+) -> Entity {
+  let status = PlayerStatus::Stand(Direction8::South);
 
   let tile = Tile::new(status.clone());
   let pos = TilePosition {
-    x:     0,
+    x:     id.0 as i64 * 2,
     y:     0,
     layer: 1,
   };
 
-  commands.spawn((
-    SpriteSheetBundle {
-      texture_atlas: tile._type.atlas_handle(&atlases).atlas,
-      transform: pos.transform(&tile._type),
-      sprite: tile.texture_atlas_sprite(&atlases),
-      ..Default::default()
-    },
-    Name::new("player"),
-    Player(status),
-  ));
+  commands
+    .spawn((
+      SpriteSheetBundle {
+        texture_atlas: tile._type.atlas_handle(atlases).atlas,
+        transform: pos.transform(&tile._type),
+        sprite: tile.texture_atlas_sprite(atlases),
+        ..Default::default()
+      },
+      Name::new(format!("player {}", id.0 + 1)),
+      Player(status),
+      Velocity::default(),
+      id,
+      input,
+      Inventory::default(),
+      HotbarSelection::default(),
+      Equipment::default(),
+      StatsBundle::default(),
+      InteractionFocus::default(),
+      Health::default(),
+      Stamina::default(),
+      RunEntity,
+    ))
+    .id()
+}
+
+/// Gives every newly connected gamepad its own player. Outside of a run the
+/// gamepad is only remembered, and its player joins when the run starts.
+fn join_gamepad_players(
+  mut commands: Commands,
+  mut events: EventReader<GamepadConnectionEvent>,
+  mut local_players: ResMut<LocalPlayers>,
+  atlases: Option<Res<TileAtlases>>,
+  run: Option<Res<Run>>,
+) {
+  for event in events.read() {
+    if !event.connected() {
+      continue;
+    }
+    let already_bound = local_players.0.iter().any(|input| {
+      matches!(input, PlayerInput::Gamepad(gamepad) if *gamepad == event.gamepad)
+    });
+    if already_bound {
+      continue;
+    }
+    let input = PlayerInput::Gamepad(event.gamepad);
+    let id = PlayerId(local_players.0.len());
+    local_players.0.push(input.clone());
+    if let (Some(_), Some(atlases)) = (&run, &atlases) {
+      spawn_player(&mut commands, atlases, id, input);
+    }
+  }
 }
 
 #[allow(clippy::type_complexity)]
-fn update_player_sprite(
+pub fn update_player_sprite(
   mut commands: Commands,
   mut query: Query<
     (
@@ -85,12 +186,14 @@ fn update_player_sprite(
   >,
   atlases: Res<TileAtlases>,
   time: Res<Time>,
+  mut events: EventWriter<AnimationEvent>,
+  mut completions: EventWriter<AnimationComplete>,
 ) {
   for (entity, player, mut sprite, tile) in query.iter_mut() {
-    if let Some(mut tile) = tile {
+    let progress = if let Some(mut tile) = tile {
       // if the status matches, just tick the tile
-      if tile.tile._type == player.0 {
-        tile.tick(time.delta_seconds());
+      let progress = if tile.tile._type == player.0 {
+        tile.tick(time.delta_seconds())
       } else {
         // if the style doesn't match, reset the tick unless we're going
         // from a run or walk to another run or walk
@@ -101,68 +204,96 @@ fn update_player_sprite(
         let new_is_run_or_walk =
           matches!(player.0, PlayerStatus::Walk(_) | PlayerStatus::Run(_));
         if old_was_run_or_walk && new_is_run_or_walk {
-          let mut new_tile = AnimatedTile::new(Tile::new(player.0.clone()));
-          new_tile.time = tile.time;
-          *tile = new_tile;
-          tile.tick(time.delta_seconds());
+          *tile = tile.continue_as(player.0.clone(), &atlases);
+          tile.tick(time.delta_seconds())
         } else {
-          *tile = AnimatedTile::new(Tile::new(player.0.clone()));
+          *tile = AnimatedTile::new(Tile::new(player.0.clone()), &atlases);
+          AnimationProgress {
+            events: tile.events_at(0),
+            ..default()
+          }
         }
-      }
-      *sprite = tile.tile.texture_atlas_sprite(&atlases);
+      };
+      *sprite = tile.texture_atlas_sprite(&atlases);
+      progress
     } else {
-      let tile = AnimatedTile::new(Tile::new(player.0.clone()));
-      *sprite = tile.tile.texture_atlas_sprite(&atlases);
+      let tile = AnimatedTile::new(Tile::new(player.0.clone()), &atlases);
+      *sprite = tile.texture_atlas_sprite(&atlases);
+      let progress = AnimationProgress {
+        events: tile.events_at(0),
+        ..default()
+      };
       commands.entity(entity).insert(tile);
+      progress
+    };
+    events.send_batch(
+      progress
+        .events
+        .into_iter()
+        .map(|name| AnimationEvent { entity, name }),
+    );
+    if progress.completed {
+      completions.send(AnimationComplete { entity });
     }
   }
 }
 
 fn accept_movement_input(
-  keyboard_input: Res<Input<KeyCode>>,
-  speeds: Res<PlayerSpeeds>,
-  mut query: Query<&mut Player>,
+  inputs: InputSources,
+  mut query: Query<(&mut Player, &PlayerInput, &Stats, &Stamina)>,
 ) {
-  for mut player in query.iter_mut() {
-    let mut movement = Vec2::ZERO;
-    if keyboard_input.pressed(KeyCode::W) {
-      movement.y += 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::S) {
-      movement.y -= 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::D) {
-      movement.x += 1.0;
-    }
-    if keyboard_input.pressed(KeyCode::A) {
-      movement.x -= 1.0;
-    }
-    movement = movement.normalize_or_zero();
-    let run = keyboard_input.pressed(KeyCode::ShiftLeft);
+  for (mut player, input, stats, stamina) in query.iter_mut() {
+    let movement = inputs.movement(input);
+    let run = inputs.run(input) && stamina.current > 0.0;
 
     let old_status = player.0.clone();
     if movement != Vec2::ZERO {
       if run {
-        player.0 = PlayerStatus::Run(movement * speeds.run);
+        player.0 = PlayerStatus::Run(movement * stats.run);
       } else {
-        player.0 = PlayerStatus::Walk(movement * speeds.walk);
+        player.0 = PlayerStatus::Walk(movement * stats.walk);
       }
     } else {
-      player.0 = PlayerStatus::Stand(old_status.direction());
+      player.0 = PlayerStatus::Stand(old_status.facing());
+    }
+  }
+}
+
+/// Stops every player while movement input is ignored, so nobody keeps walking
+/// through a conversation.
+fn hold_players(mut players: Query<&mut Player>) {
+  for mut player in players.iter_mut() {
+    let status = PlayerStatus::Stand(player.0.facing());
+    if player.0 != status {
+      player.0 = status;
     }
   }
 }
 
 pub fn apply_movement(
-  mut query: Query<(&mut Transform, &Player)>,
+  mut query: Query<(&mut Transform, &mut Velocity, &Player)>,
+  speeds: Res<PlayerSpeeds>,
+  ground: Res<Ground>,
   time: Res<Time>,
 ) {
-  for (mut transform, player) in query.iter_mut() {
-    match player.0 {
-      PlayerStatus::Walk(movement) | PlayerStatus::Run(movement) => {
-        transform.translation += movement.extend(0.0) * time.delta_seconds();
-      }
-      _ => {}
+  let delta = time.delta_seconds();
+  for (mut transform, mut velocity, player) in query.iter_mut() {
+    let target = match player.0 {
+      PlayerStatus::Walk(movement) | PlayerStatus::Run(movement) => movement,
+      PlayerStatus::Stand(_) => Vec2::ZERO,
+    };
+    let terrain = ground.terrain_at(transform.translation.xy());
+    let steered = steer(
+      velocity.0,
+      target * terrain.speed,
+      &terrain.grip(&speeds),
+      delta,
+    );
+    if steered != velocity.0 {
+      velocity.0 = steered;
+    }
+    if velocity.0 != Vec2::ZERO {
+      transform.translation += velocity.0.extend(0.0) * delta;
     }
   }
 }
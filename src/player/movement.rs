@@ -0,0 +1,66 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+/// How a player's velocity chases the speed and direction they ask for.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct PlayerSpeeds {
+  /// How fast a player speeds up, in units per second squared.
+  pub acceleration: f32,
+  /// How fast a player slows down when they let go or turn back, in units per
+  /// second squared.
+  pub deceleration: f32,
+  /// How fast a player turns while moving, in radians per second.
+  pub turn_rate:    f32,
+}
+
+impl Default for PlayerSpeeds {
+  fn default() -> Self {
+    PlayerSpeeds {
+      acceleration: 24.0,
+      deceleration: 20.0,
+      turn_rate:    14.0,
+    }
+  }
+}
+
+/// How fast and which way a player is actually moving, in units per second.
+/// `PlayerStatus` only says where they want to go.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Velocity(pub Vec2);
+
+/// Moves `velocity` towards `target` over `delta` seconds.
+pub fn steer(
+  velocity: Vec2,
+  target: Vec2,
+  speeds: &PlayerSpeeds,
+  delta: f32,
+) -> Vec2 {
+  let speed = velocity.length();
+  let Some(wanted) = target.try_normalize() else {
+    let speed = (speed - speeds.deceleration * delta).max(0.0);
+    return velocity.normalize_or_zero() * speed;
+  };
+  let Some(heading) = velocity.try_normalize() else {
+    return wanted * (speeds.acceleration * delta).min(target.length());
+  };
+
+  let turn = heading.angle_between(wanted);
+  let max_turn = speeds.turn_rate * delta;
+  let heading =
+    Vec2::from_angle(turn.clamp(-max_turn, max_turn)).rotate(heading);
+  // brake through sharp turns rather than swinging round at full speed
+  let target_speed = if turn.abs() - max_turn > FRAC_PI_2 {
+    0.0
+  } else {
+    target.length()
+  };
+  let speed = if speed < target_speed {
+    (speed + speeds.acceleration * delta).min(target_speed)
+  } else {
+    (speed - speeds.deceleration * delta).max(target_speed)
+  };
+  heading * speed
+}
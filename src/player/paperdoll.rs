@@ -0,0 +1,176 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{status::PlayerStatus, Player};
+use crate::tile::{TileAtlases, TileType};
+
+/// An equipment slot drawn over the body sheet. Slots are drawn in
+/// declaration order, so later slots cover earlier ones.
+#[derive(Clone, Copy, Debug, Reflect, PartialEq, Eq, Hash)]
+pub enum PaperdollSlot {
+  Feet,
+  Legs,
+  Torso,
+  Hands,
+  Outer,
+  Hair,
+  Head,
+  Weapon,
+}
+
+impl PaperdollSlot {
+  /// The depth of this layer above the body, in the body's local space.
+  fn depth(&self) -> f32 { (*self as u8 + 1) as f32 * 0.01 }
+}
+
+/// A sprite layer drawn on top of its parent's body sprite. The layer's
+/// sheet shares the body's layout, so it always shows the body's frame.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct PaperdollLayer {
+  pub slot: PaperdollSlot,
+  pub tint: Color,
+}
+
+impl Default for PaperdollLayer {
+  fn default() -> Self {
+    Self {
+      slot: PaperdollSlot::Torso,
+      tint: Color::WHITE,
+    }
+  }
+}
+
+/// Sets, retints or clears (with `sheet: None`) a paperdoll slot on a player.
+#[derive(Event, Clone)]
+pub struct SetPaperdollLayer {
+  pub player: Entity,
+  pub slot:   PaperdollSlot,
+  /// The path of a sheet laid out like the body sheet.
+  pub sheet:  Option<String>,
+  pub tint:   Color,
+}
+
+/// Layer atlases by sheet path, so each sheet is only built once.
+#[derive(Resource, Default)]
+pub struct PaperdollSheets(HashMap<String, Handle<TextureAtlas>>);
+
+impl PaperdollSheets {
+  fn get_or_load(
+    &mut self,
+    path: &str,
+    body_atlas: &Handle<TextureAtlas>,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+  ) -> Option<Handle<TextureAtlas>> {
+    if let Some(handle) = self.0.get(path) {
+      return Some(handle.clone());
+    }
+    // reuse the body's cells so the layer can never drift out of sync
+    let body = texture_atlases.get(body_atlas)?;
+    let mut atlas =
+      TextureAtlas::new_empty(asset_server.load(path.to_string()), body.size);
+    for rect in body.textures.clone() {
+      atlas.add_texture(rect);
+    }
+    let handle = texture_atlases.add(atlas);
+    self.0.insert(path.to_string(), handle.clone());
+    Some(handle)
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_paperdoll_layers(
+  mut commands: Commands,
+  mut events: EventReader<SetPaperdollLayer>,
+  players: Query<(&TextureAtlasSprite, Option<&Children>), With<Player>>,
+  layers: Query<&PaperdollLayer>,
+  mut sheets: ResMut<PaperdollSheets>,
+  atlases: Res<TileAtlases>,
+  asset_server: Res<AssetServer>,
+  mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+  for event in events.read() {
+    let Ok((body_sprite, children)) = players.get(event.player) else {
+      continue;
+    };
+
+    // drop whatever currently occupies the slot
+    for &child in children.into_iter().flatten() {
+      if layers
+        .get(child)
+        .is_ok_and(|layer| layer.slot == event.slot)
+      {
+        commands.entity(child).despawn_recursive();
+      }
+    }
+
+    let Some(path) = &event.sheet else {
+      continue;
+    };
+    let Some(sheet) = sheets.get_or_load(
+      path,
+      &PlayerStatus::default().atlas_handle(&atlases).atlas,
+      &asset_server,
+      &mut texture_atlases,
+    ) else {
+      warn!("cannot build paperdoll layer {path:?} before the body atlas");
+      continue;
+    };
+
+    let layer = commands
+      .spawn((
+        SpriteSheetBundle {
+          texture_atlas: sheet,
+          transform: Transform::from_xyz(0.0, 0.0, event.slot.depth()),
+          sprite: TextureAtlasSprite {
+            color: event.tint,
+            ..body_sprite.clone()
+          },
+          ..Default::default()
+        },
+        Name::new(format!("{:?} layer", event.slot)),
+        PaperdollLayer {
+          slot: event.slot,
+          tint: event.tint,
+        },
+      ))
+      .id();
+    commands.entity(event.player).add_child(layer);
+  }
+}
+
+/// Reapplies tints edited in place, e.g. from the inspector.
+pub fn tint_paperdoll_layers(
+  mut layers: Query<
+    (&PaperdollLayer, &mut TextureAtlasSprite),
+    Changed<PaperdollLayer>,
+  >,
+) {
+  for (layer, mut sprite) in layers.iter_mut() {
+    sprite.color = layer.tint;
+  }
+}
+
+/// Copies the body's current frame onto each of its layers.
+#[allow(clippy::type_complexity)]
+pub fn sync_paperdoll_layers(
+  players: Query<
+    (&TextureAtlasSprite, &Children),
+    (With<Player>, Changed<TextureAtlasSprite>),
+  >,
+  mut layers: Query<
+    (&PaperdollLayer, &mut TextureAtlasSprite),
+    Without<Player>,
+  >,
+) {
+  for (body_sprite, children) in players.iter() {
+    for &child in children.iter() {
+      if let Ok((layer, mut sprite)) = layers.get_mut(child) {
+        *sprite = TextureAtlasSprite {
+          color: layer.tint,
+          ..body_sprite.clone()
+        };
+      }
+    }
+  }
+}
@@ -1,28 +1,33 @@
 use bevy::prelude::*;
 
-use crate::tile::{
-  Direction4, TextureAtlasWithGrid, TileAtlases, TileSheetCoords, TileType,
-};
+use crate::tile::{Direction4, Direction8, TileType};
 
 #[derive(Clone, Reflect, PartialEq)]
 pub enum PlayerStatus {
-  Stand(Direction4),
+  Stand(Direction8),
   Walk(Vec2),
   Run(Vec2),
 }
 
 impl Default for PlayerStatus {
-  fn default() -> Self { PlayerStatus::Stand(Direction4::South) }
+  fn default() -> Self { PlayerStatus::Stand(Direction8::South) }
 }
 
 impl PlayerStatus {
-  pub fn direction(&self) -> Direction4 {
+  /// The way the player faces, including diagonals.
+  pub fn facing(&self) -> Direction8 {
     match self {
       PlayerStatus::Stand(dir) => *dir,
-      PlayerStatus::Walk(dir) => {
-        Direction4::try_from(*dir).unwrap_or(Direction4::South)
+      PlayerStatus::Walk(dir) | PlayerStatus::Run(dir) => {
+        Direction8::try_from(*dir).unwrap_or_default()
       }
-      PlayerStatus::Run(dir) => {
+    }
+  }
+  /// The way the player faces, for sheets without diagonals.
+  pub fn direction(&self) -> Direction4 {
+    match self {
+      PlayerStatus::Stand(dir) => (*dir).into(),
+      PlayerStatus::Walk(dir) | PlayerStatus::Run(dir) => {
         Direction4::try_from(*dir).unwrap_or(Direction4::South)
       }
     }
@@ -32,126 +37,28 @@ impl TileType for PlayerStatus {
   fn size_and_center(&self) -> (Vec2, Vec2) {
     (Vec2::splat(16.0), Vec2::new(0.0, 0.5))
   }
-  fn coords(&self) -> Vec<TileSheetCoords> {
-    match self {
-      PlayerStatus::Stand(dir) => match dir {
-        Direction4::North => {
-          vec![TileSheetCoords::new(0, 1)]
-        }
-        Direction4::East => {
-          vec![TileSheetCoords::new(0, 2)]
-        }
-        Direction4::South => {
-          vec![TileSheetCoords::new(0, 0)]
-        }
-        Direction4::West => {
-          vec![TileSheetCoords::new(0, 2).flip_x()]
-        }
-      },
-      PlayerStatus::Walk(dir) => {
-        let Ok(dir) = Direction4::try_from(*dir) else {
-          return vec![TileSheetCoords::new(0, 0)];
-        };
-        match dir {
-          Direction4::North => {
-            vec![
-              TileSheetCoords::new(4, 3),
-              TileSheetCoords::new(5, 3),
-              TileSheetCoords::new(6, 3),
-              TileSheetCoords::new(4, 3).flip_x(),
-              TileSheetCoords::new(5, 3).flip_x(),
-              TileSheetCoords::new(6, 3).flip_x(),
-            ]
-          }
-          Direction4::East => {
-            vec![
-              TileSheetCoords::new(0, 4),
-              TileSheetCoords::new(1, 4),
-              TileSheetCoords::new(2, 4),
-              TileSheetCoords::new(3, 4),
-              TileSheetCoords::new(4, 4),
-              TileSheetCoords::new(5, 4),
-            ]
-          }
-          Direction4::South => {
-            vec![
-              TileSheetCoords::new(0, 3),
-              TileSheetCoords::new(1, 3),
-              TileSheetCoords::new(2, 3),
-              TileSheetCoords::new(0, 3).flip_x(),
-              TileSheetCoords::new(1, 3).flip_x(),
-              TileSheetCoords::new(2, 3).flip_x(),
-            ]
-          }
-          Direction4::West => {
-            vec![
-              TileSheetCoords::new(0, 4).flip_x(),
-              TileSheetCoords::new(1, 4).flip_x(),
-              TileSheetCoords::new(2, 4).flip_x(),
-              TileSheetCoords::new(3, 4).flip_x(),
-              TileSheetCoords::new(4, 4).flip_x(),
-              TileSheetCoords::new(5, 4).flip_x(),
-            ]
-          }
-        }
-      }
-      PlayerStatus::Run(dir) => {
-        let Ok(dir) = Direction4::try_from(*dir) else {
-          return vec![TileSheetCoords::new(0, 0)];
-        };
-        match dir {
-          Direction4::North => {
-            vec![
-              TileSheetCoords::new(4, 3),
-              TileSheetCoords::new(5, 3),
-              TileSheetCoords::new(7, 3),
-              TileSheetCoords::new(4, 3).flip_x(),
-              TileSheetCoords::new(5, 3).flip_x(),
-              TileSheetCoords::new(7, 3).flip_x(),
-            ]
-          }
-          Direction4::East => {
-            vec![
-              TileSheetCoords::new(0, 4),
-              TileSheetCoords::new(1, 4),
-              TileSheetCoords::new(6, 4),
-              TileSheetCoords::new(3, 4),
-              TileSheetCoords::new(4, 4),
-              TileSheetCoords::new(7, 4),
-            ]
-          }
-          Direction4::South => {
-            vec![
-              TileSheetCoords::new(0, 3),
-              TileSheetCoords::new(1, 3),
-              TileSheetCoords::new(3, 3),
-              TileSheetCoords::new(0, 3).flip_x(),
-              TileSheetCoords::new(1, 3).flip_x(),
-              TileSheetCoords::new(3, 3).flip_x(),
-            ]
-          }
-          Direction4::West => {
-            vec![
-              TileSheetCoords::new(0, 4).flip_x(),
-              TileSheetCoords::new(1, 4).flip_x(),
-              TileSheetCoords::new(6, 4).flip_x(),
-              TileSheetCoords::new(3, 4).flip_x(),
-              TileSheetCoords::new(4, 4).flip_x(),
-              TileSheetCoords::new(7, 4).flip_x(),
-            ]
-          }
-        }
-      }
+  fn atlas_id(&self) -> &str { "player_base" }
+  fn animations(&self) -> Vec<String> {
+    let action = match self {
+      PlayerStatus::Walk(dir) if *dir != Vec2::ZERO => "walk",
+      PlayerStatus::Run(dir) if *dir != Vec2::ZERO => "run",
+      _ => "stand",
+    };
+    let facing = self.facing();
+    let mut names = vec![format!("{action}_{}", facing.name())];
+    // sheets without diagonal frames show the nearest of the four directions
+    if facing.is_diagonal() {
+      names.push(format!("{action}_{}", self.direction().name()));
     }
+    names
   }
-  fn atlas_handle(&self, atlases: &TileAtlases) -> TextureAtlasWithGrid {
-    atlases.player_base.clone()
-  }
-  fn anim_speed(&self) -> Option<f32> {
+  fn frame_events(&self) -> Vec<(usize, &'static str)> {
     match self {
-      PlayerStatus::Walk(_) => Some(8.0),
-      PlayerStatus::Run(_) => Some(8.0),
-      _ => None,
+      // a foot comes down at the start of each half of the cycle
+      PlayerStatus::Walk(_) | PlayerStatus::Run(_) => {
+        vec![(0, "footstep"), (3, "footstep")]
+      }
+      _ => Vec::new(),
     }
   }
 }
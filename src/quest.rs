@@ -0,0 +1,381 @@
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  data::RonAssetLoader,
+  flags::{Condition, FlagEffect, GameFlags},
+  interaction::{InteractionEvent, InteractionKind},
+  item::{AddItem, Inventory, ItemId, ItemStack},
+  player::{Player, PlayerId},
+  state::{EndRun, GameplaySet},
+};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct QuestDefinition {
+  pub name:        String,
+  #[serde(default)]
+  pub description: String,
+  /// Starts the quest as soon as it holds. Without one, the quest has to be
+  /// started with `StartQuest`.
+  #[serde(default)]
+  pub start:       Option<Condition>,
+  pub objectives:  Vec<Objective>,
+  #[serde(default)]
+  pub rewards:     Vec<Reward>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Objective {
+  /// Stand anywhere in the rectangle of tile positions, corners inclusive.
+  Reach { min: (i64, i64), max: (i64, i64) },
+  /// Defeat enemies of the given kind.
+  Kill { enemy: String, count: u32 },
+  /// Hold this many of an item at once.
+  Collect { item: ItemId, count: u32 },
+  /// Talk to the entity with this name.
+  TalkTo { npc: String },
+}
+
+impl Objective {
+  /// The progress at which this objective is done.
+  pub fn target(&self) -> u32 {
+    match self {
+      Objective::Reach { .. } | Objective::TalkTo { .. } => 1,
+      Objective::Kill { count, .. } | Objective::Collect { count, .. } => {
+        *count
+      }
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum Reward {
+  /// Given to the first player.
+  Item(ItemStack),
+  Flag(FlagEffect),
+}
+
+/// Every quest definition in a `.quests.ron` file, by id.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct QuestBook {
+  pub quests: HashMap<String, QuestDefinition>,
+}
+
+#[derive(Resource)]
+pub struct QuestBookHandle(pub Handle<QuestBook>);
+
+impl FromWorld for QuestBookHandle {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    QuestBookHandle(asset_server.load("data/base.quests.ron"))
+  }
+}
+
+/// Read access to the loaded quest definitions.
+#[derive(SystemParam)]
+pub struct Quests<'w> {
+  handle: Res<'w, QuestBookHandle>,
+  books:  Res<'w, Assets<QuestBook>>,
+}
+
+impl Quests<'_> {
+  pub fn get(&self, quest: &str) -> Option<&QuestDefinition> {
+    self.books.get(&self.handle.0)?.quests.get(quest)
+  }
+  pub fn iter(&self) -> impl Iterator<Item = (&String, &QuestDefinition)> {
+    self
+      .books
+      .get(&self.handle.0)
+      .into_iter()
+      .flat_map(|b| &b.quests)
+  }
+}
+
+#[derive(
+  Clone, Copy, Debug, Reflect, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum QuestStatus {
+  Active,
+  Completed,
+}
+
+#[derive(Clone, Debug, Reflect, Serialize, Deserialize)]
+pub struct QuestState {
+  pub status:   QuestStatus,
+  /// Progress towards each objective, in definition order.
+  pub progress: Vec<u32>,
+}
+
+/// Every quest that has been started, by id.
+#[derive(Resource, Clone, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+pub struct QuestLog(pub HashMap<String, QuestState>);
+
+impl QuestLog {
+  pub fn status(&self, quest: &str) -> Option<QuestStatus> {
+    self.0.get(quest).map(|state| state.status)
+  }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct StartQuest(pub String);
+
+/// Sent by combat when an enemy is defeated.
+#[derive(Event, Clone, Debug)]
+pub struct EnemyKilled {
+  pub enemy: String,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct QuestStarted(pub String);
+
+#[derive(Event, Clone, Debug)]
+pub struct QuestProgressed {
+  pub quest:     String,
+  pub objective: usize,
+  pub progress:  u32,
+  pub target:    u32,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct QuestCompleted(pub String);
+
+/// Raises an objective's progress, never lowering it or passing its target.
+fn advance(
+  state: &mut QuestState,
+  quest: &str,
+  objective: usize,
+  target: u32,
+  progress: u32,
+  events: &mut EventWriter<QuestProgressed>,
+) {
+  let progress = progress.min(target);
+  if state.status != QuestStatus::Active
+    || progress <= state.progress[objective]
+  {
+    return;
+  }
+  state.progress[objective] = progress;
+  events.send(QuestProgressed {
+    quest: quest.to_string(),
+    objective,
+    progress,
+    target,
+  });
+}
+
+fn start_quests(
+  mut requests: EventReader<StartQuest>,
+  mut log: ResMut<QuestLog>,
+  mut started: EventWriter<QuestStarted>,
+  quests: Quests,
+  flags: Res<GameFlags>,
+) {
+  let requested = requests.read().map(|r| r.0.clone()).collect::<Vec<_>>();
+  for (id, definition) in quests.iter() {
+    if log.0.contains_key(id) {
+      continue;
+    }
+    let triggered = definition
+      .start
+      .as_ref()
+      .is_some_and(|c| c.evaluate(&flags));
+    if !triggered && !requested.contains(id) {
+      continue;
+    }
+    log.0.insert(id.clone(), QuestState {
+      status:   QuestStatus::Active,
+      progress: vec![0; definition.objectives.len()],
+    });
+    started.send(QuestStarted(id.clone()));
+  }
+}
+
+fn track_events(
+  mut kills: EventReader<EnemyKilled>,
+  mut interactions: EventReader<InteractionEvent>,
+  names: Query<&Name>,
+  mut log: ResMut<QuestLog>,
+  mut progressed: EventWriter<QuestProgressed>,
+  quests: Quests,
+) {
+  let kills = kills.read().map(|k| k.enemy.clone()).collect::<Vec<_>>();
+  let talked_to = interactions
+    .read()
+    .filter(|i| i.kind == InteractionKind::Talk)
+    .filter_map(|i| names.get(i.target).ok())
+    .map(|name| name.as_str().to_string())
+    .collect::<Vec<_>>();
+  if kills.is_empty() && talked_to.is_empty() {
+    return;
+  }
+
+  for (id, state) in log.0.iter_mut() {
+    let Some(definition) = quests.get(id) else {
+      continue;
+    };
+    for (index, objective) in definition.objectives.iter().enumerate() {
+      let progress = match objective {
+        Objective::Kill { enemy, .. } => {
+          let count = kills.iter().filter(|k| *k == enemy).count() as u32;
+          state.progress[index] + count
+        }
+        Objective::TalkTo { npc } if talked_to.contains(npc) => 1,
+        _ => continue,
+      };
+      advance(
+        state,
+        id,
+        index,
+        objective.target(),
+        progress,
+        &mut progressed,
+      );
+    }
+  }
+}
+
+fn poll_objectives(
+  players: Query<(&Transform, &Inventory), With<Player>>,
+  mut log: ResMut<QuestLog>,
+  mut progressed: EventWriter<QuestProgressed>,
+  quests: Quests,
+) {
+  for (id, state) in log.0.iter_mut() {
+    if state.status != QuestStatus::Active {
+      continue;
+    }
+    let Some(definition) = quests.get(id) else {
+      continue;
+    };
+    for (index, objective) in definition.objectives.iter().enumerate() {
+      let progress = match objective {
+        Objective::Reach { min, max } => {
+          let inside = players.iter().any(|(transform, _)| {
+            let pos = transform.translation.xy().round();
+            (min.0 as f32..=max.0 as f32).contains(&pos.x)
+              && (min.1 as f32..=max.1 as f32).contains(&pos.y)
+          });
+          inside as u32
+        }
+        Objective::Collect { item, .. } => players
+          .iter()
+          .map(|(_, inventory)| inventory.count(item))
+          .max()
+          .unwrap_or_default(),
+        _ => continue,
+      };
+      advance(
+        state,
+        id,
+        index,
+        objective.target(),
+        progress,
+        &mut progressed,
+      );
+    }
+  }
+}
+
+fn complete_quests(
+  mut log: ResMut<QuestLog>,
+  mut flags: ResMut<GameFlags>,
+  mut completed: EventWriter<QuestCompleted>,
+  mut items: EventWriter<AddItem>,
+  players: Query<(Entity, &PlayerId)>,
+  quests: Quests,
+) {
+  let leader = players
+    .iter()
+    .min_by_key(|(_, id)| **id)
+    .map(|(entity, _)| entity);
+
+  for (id, state) in log.0.iter_mut() {
+    if state.status != QuestStatus::Active {
+      continue;
+    }
+    let Some(definition) = quests.get(id) else {
+      continue;
+    };
+    let done = definition
+      .objectives
+      .iter()
+      .zip(&state.progress)
+      .all(|(objective, progress)| *progress >= objective.target());
+    if !done {
+      continue;
+    }
+
+    state.status = QuestStatus::Completed;
+    for reward in &definition.rewards {
+      match reward {
+        Reward::Item(stack) => {
+          if let Some(entity) = leader {
+            items.send(AddItem {
+              entity,
+              stack: stack.clone(),
+            });
+          }
+        }
+        Reward::Flag(effect) => effect.apply(&mut flags),
+      }
+    }
+    completed.send(QuestCompleted(id.clone()));
+  }
+}
+
+/// Pads or cuts each quest's progress to the objectives in its definition, so
+/// logs saved before a quest book changed, or kept across a hot reload of it,
+/// still have an entry for every objective.
+fn fit_progress(
+  mut reloads: EventReader<AssetEvent<QuestBook>>,
+  mut log: ResMut<QuestLog>,
+  quests: Quests,
+) {
+  let reloaded = reloads.read().count() > 0;
+  if !reloaded && !log.is_changed() {
+    return;
+  }
+  for (id, state) in log.0.iter_mut() {
+    let Some(definition) = quests.get(id) else {
+      continue;
+    };
+    if state.progress.len() != definition.objectives.len() {
+      state.progress.resize(definition.objectives.len(), 0);
+    }
+  }
+}
+
+fn reset_quest_log(mut log: ResMut<QuestLog>) { *log = QuestLog::default(); }
+
+pub struct QuestPlugin;
+
+impl Plugin for QuestPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<QuestLog>()
+      .init_asset::<QuestBook>()
+      .register_asset_loader(RonAssetLoader::<QuestBook>::new(&["quests.ron"]))
+      .init_resource::<QuestBookHandle>()
+      .init_resource::<QuestLog>()
+      .add_event::<StartQuest>()
+      .add_event::<EnemyKilled>()
+      .add_event::<QuestStarted>()
+      .add_event::<QuestProgressed>()
+      .add_event::<QuestCompleted>()
+      .add_systems(
+        Update,
+        (
+          fit_progress,
+          start_quests,
+          track_events,
+          poll_objectives,
+          complete_quests,
+        )
+          .chain()
+          .after(crate::interaction::interact)
+          .in_set(GameplaySet),
+      )
+      .add_systems(EndRun, reset_quest_log);
+  }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  clock::WorldClock,
+  flags::GameFlags,
+  item::{Equipment, Inventory},
+  player::{Player, PlayerId},
+  quest::QuestLog,
+  state::GameplaySet,
+  stats::BaseStats,
+};
+
+/// Where the save file is written, relative to the working directory.
+const SAVE_PATH: &str = "save.ron";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SaveGame {
+  #[serde(default)]
+  pub players: Vec<PlayerSave>,
+  #[serde(default)]
+  pub flags:   GameFlags,
+  #[serde(default)]
+  pub quests:  QuestLog,
+  #[serde(default)]
+  pub clock:   Option<ClockSave>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave {
+  pub id:        usize,
+  pub position:  [f32; 2],
+  pub inventory: Inventory,
+  #[serde(default)]
+  pub equipment: Equipment,
+  #[serde(default)]
+  pub stats:     BaseStats,
+}
+
+/// The time of day. The day length is configuration, not progress, so it isn't
+/// saved.
+#[derive(Serialize, Deserialize)]
+pub struct ClockSave {
+  pub day:  u32,
+  pub time: f32,
+}
+
+/// Asks for the game to be written to the save file.
+#[derive(Event, Clone, Default)]
+pub struct SaveRequest;
+
+/// Asks for the game to be restored from the save file.
+#[derive(Event, Clone, Default)]
+pub struct LoadRequest;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_event::<SaveRequest>()
+      .add_event::<LoadRequest>()
+      .add_systems(
+        Update,
+        (save_load_keys, save_game, load_game)
+          .chain()
+          .in_set(GameplaySet),
+      );
+  }
+}
+
+fn save_load_keys(
+  keyboard_input: Res<Input<KeyCode>>,
+  mut saves: EventWriter<SaveRequest>,
+  mut loads: EventWriter<LoadRequest>,
+) {
+  if keyboard_input.just_pressed(KeyCode::F5) {
+    saves.send(SaveRequest);
+  }
+  if keyboard_input.just_pressed(KeyCode::F9) {
+    loads.send(LoadRequest);
+  }
+}
+
+fn save_game(
+  mut requests: EventReader<SaveRequest>,
+  players: Query<
+    (&PlayerId, &Transform, &Inventory, &Equipment, &BaseStats),
+    With<Player>,
+  >,
+  flags: Res<GameFlags>,
+  quests: Res<QuestLog>,
+  clock: Res<WorldClock>,
+) {
+  if requests.read().count() == 0 {
+    return;
+  }
+
+  let save = SaveGame {
+    players: players
+      .iter()
+      .map(|(id, transform, inventory, equipment, stats)| PlayerSave {
+        id:        id.0,
+        position:  transform.translation.xy().to_array(),
+        inventory: inventory.clone(),
+        equipment: equipment.clone(),
+        stats:     stats.clone(),
+      })
+      .collect(),
+    flags:   flags.clone(),
+    quests:  quests.clone(),
+    clock:   Some(ClockSave {
+      day:  clock.day,
+      time: clock.time,
+    }),
+  };
+
+  let result = ron::ser::to_string_pretty(&save, Default::default())
+    .map_err(|e| e.to_string())
+    .and_then(|text| fs::write(SAVE_PATH, text).map_err(|e| e.to_string()));
+  match result {
+    Ok(()) => info!("saved game to {SAVE_PATH}"),
+    Err(e) => error!("failed to save game: {e}"),
+  }
+}
+
+fn load_game(
+  mut requests: EventReader<LoadRequest>,
+  mut players: Query<
+    (
+      &PlayerId,
+      &mut Transform,
+      &mut Inventory,
+      &mut Equipment,
+      &mut BaseStats,
+    ),
+    With<Player>,
+  >,
+  mut flags: ResMut<GameFlags>,
+  mut quests: ResMut<QuestLog>,
+  mut clock: ResMut<WorldClock>,
+) {
+  if requests.read().count() == 0 {
+    return;
+  }
+
+  let result = fs::read_to_string(SAVE_PATH)
+    .map_err(|e| e.to_string())
+    .and_then(|text| {
+      ron::from_str::<SaveGame>(&text).map_err(|e| e.to_string())
+    });
+  let save = match result {
+    Ok(save) => save,
+    Err(e) => {
+      error!("failed to load game: {e}");
+      return;
+    }
+  };
+
+  for (id, mut transform, mut inventory, mut equipment, mut stats) in
+    players.iter_mut()
+  {
+    let Some(player_save) = save.players.iter().find(|p| p.id == id.0) else {
+      continue;
+    };
+    let position = Vec2::from_array(player_save.position);
+    transform.translation = position.extend(transform.translation.z);
+    *inventory = player_save.inventory.clone();
+    *equipment = player_save.equipment.clone();
+    *stats = player_save.stats.clone();
+  }
+  *flags = save.flags;
+  *quests = save.quests;
+  if let Some(saved) = save.clock {
+    clock.day = saved.day;
+    clock.time = saved.time.rem_euclid(1.0);
+  }
+  info!("loaded game from {SAVE_PATH}");
+}
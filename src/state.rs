@@ -0,0 +1,224 @@
+use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
+
+#[derive(States, Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum AppState {
+  #[default]
+  Boot,
+  Loading,
+  MainMenu,
+  Playing,
+  Paused,
+  GameOver,
+}
+
+/// Systems that drive the game world. They only run while `Playing`.
+#[derive(SystemSet, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct GameplaySet;
+
+/// Run when entering `Playing` with no run in progress, to build the world.
+#[derive(ScheduleLabel, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct StartRun;
+
+/// Run when a run is abandoned or over, to tear the world back down.
+#[derive(ScheduleLabel, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct EndRun;
+
+/// Present while a run is in progress, including while it's paused.
+#[derive(Resource)]
+pub struct Run;
+
+/// Despawned when the run ends.
+#[derive(Component, Default)]
+pub struct RunEntity;
+
+/// Marks a menu screen, despawned when its state is left.
+#[derive(Component)]
+struct Screen;
+
+fn begin_run(world: &mut World) {
+  if world.contains_resource::<Run>() {
+    return;
+  }
+  world.insert_resource(Run);
+  world.run_schedule(StartRun);
+}
+
+fn end_run(world: &mut World) {
+  if world.remove_resource::<Run>().is_none() {
+    return;
+  }
+  world.run_schedule(EndRun);
+}
+
+fn despawn_run_entities(
+  mut commands: Commands,
+  query: Query<Entity, With<RunEntity>>,
+) {
+  for entity in query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn despawn_screens(mut commands: Commands, query: Query<Entity, With<Screen>>) {
+  for entity in query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn finish_boot(mut next: ResMut<NextState<AppState>>) {
+  next.set(AppState::Loading);
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) { time.pause(); }
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) { time.unpause(); }
+
+fn confirm_pressed(
+  keys: &Input<KeyCode>,
+  gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+  keys.any_just_pressed([KeyCode::Return, KeyCode::Space])
+    || gamepad_buttons
+      .get_just_pressed()
+      .any(|b| b.button_type == GamepadButtonType::South)
+}
+
+fn back_pressed(
+  keys: &Input<KeyCode>,
+  gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+  keys.just_pressed(KeyCode::Escape)
+    || gamepad_buttons
+      .get_just_pressed()
+      .any(|b| b.button_type == GamepadButtonType::Start)
+}
+
+fn main_menu_input(
+  keys: Res<Input<KeyCode>>,
+  gamepad_buttons: Res<Input<GamepadButton>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  if confirm_pressed(&keys, &gamepad_buttons) {
+    next.set(AppState::Playing);
+  }
+}
+
+fn playing_input(
+  keys: Res<Input<KeyCode>>,
+  gamepad_buttons: Res<Input<GamepadButton>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  if back_pressed(&keys, &gamepad_buttons) {
+    next.set(AppState::Paused);
+  }
+}
+
+fn paused_input(
+  keys: Res<Input<KeyCode>>,
+  gamepad_buttons: Res<Input<GamepadButton>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  if back_pressed(&keys, &gamepad_buttons) {
+    next.set(AppState::Playing);
+  } else if keys.just_pressed(KeyCode::Q) {
+    next.set(AppState::MainMenu);
+  }
+}
+
+fn game_over_input(
+  keys: Res<Input<KeyCode>>,
+  gamepad_buttons: Res<Input<GamepadButton>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  if confirm_pressed(&keys, &gamepad_buttons) {
+    next.set(AppState::Playing);
+  } else if back_pressed(&keys, &gamepad_buttons) {
+    next.set(AppState::MainMenu);
+  }
+}
+
+fn spawn_screen(commands: &mut Commands, title: &str, hint: &str) {
+  commands
+    .spawn((
+      NodeBundle {
+        style: Style {
+          width: Val::Percent(100.0),
+          height: Val::Percent(100.0),
+          flex_direction: FlexDirection::Column,
+          align_items: AlignItems::Center,
+          justify_content: JustifyContent::Center,
+          row_gap: Val::Px(16.0),
+          ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        z_index: ZIndex::Global(100),
+        ..default()
+      },
+      Name::new(format!("{title} screen")),
+      Screen,
+    ))
+    .with_children(|screen| {
+      screen.spawn(TextBundle::from_section(title, TextStyle {
+        font_size: 48.0,
+        color: Color::WHITE,
+        ..default()
+      }));
+      screen.spawn(TextBundle::from_section(hint, TextStyle {
+        font_size: 20.0,
+        color: Color::GRAY,
+        ..default()
+      }));
+    });
+}
+
+fn spawn_main_menu(mut commands: Commands) {
+  spawn_screen(&mut commands, "attk", "Press Enter to start");
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+  spawn_screen(
+    &mut commands,
+    "Paused",
+    "Esc to resume, Q to quit to the menu",
+  );
+}
+
+fn spawn_game_over(mut commands: Commands) {
+  spawn_screen(
+    &mut commands,
+    "Game Over",
+    "Enter to try again, Esc for the menu",
+  );
+}
+
+pub struct StatePlugin;
+
+impl Plugin for StatePlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_state::<AppState>()
+      .init_schedule(StartRun)
+      .init_schedule(EndRun)
+      .configure_sets(Update, GameplaySet.run_if(in_state(AppState::Playing)))
+      .add_systems(EndRun, despawn_run_entities)
+      .add_systems(OnEnter(AppState::Playing), begin_run)
+      .add_systems(OnEnter(AppState::MainMenu), (end_run, spawn_main_menu))
+      .add_systems(OnExit(AppState::GameOver), end_run)
+      .add_systems(OnEnter(AppState::Paused), (pause_time, spawn_pause_menu))
+      .add_systems(OnExit(AppState::Paused), resume_time)
+      .add_systems(OnEnter(AppState::GameOver), spawn_game_over)
+      .add_systems(OnExit(AppState::MainMenu), despawn_screens)
+      .add_systems(OnExit(AppState::Paused), despawn_screens)
+      .add_systems(OnExit(AppState::GameOver), despawn_screens)
+      .add_systems(
+        Update,
+        (
+          finish_boot.run_if(in_state(AppState::Boot)),
+          main_menu_input.run_if(in_state(AppState::MainMenu)),
+          playing_input.run_if(in_state(AppState::Playing)),
+          paused_input.run_if(in_state(AppState::Paused)),
+          game_over_input.run_if(in_state(AppState::GameOver)),
+        ),
+      );
+  }
+}
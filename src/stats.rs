@@ -0,0 +1,130 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+  Clone, Copy, Debug, Reflect, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum Stat {
+  Walk,
+  Run,
+  Attack,
+  Defence,
+}
+
+#[derive(Clone, Copy, Debug, Reflect, Serialize, Deserialize)]
+pub enum Modifier {
+  Add(f32),
+  Multiply(f32),
+}
+
+#[derive(Clone, Copy, Debug, Reflect, Serialize, Deserialize)]
+pub struct StatModifier {
+  pub stat:     Stat,
+  pub modifier: Modifier,
+}
+
+/// A character's stats. On its own this is the effective value, after all
+/// modifiers; wrapped in `BaseStats` it is the value before them.
+#[derive(Component, Clone, Debug, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Stats {
+  pub walk:    f32,
+  pub run:     f32,
+  pub attack:  f32,
+  pub defence: f32,
+}
+
+impl Default for Stats {
+  fn default() -> Self {
+    Stats {
+      walk:    3.0,
+      run:     4.0,
+      attack:  1.0,
+      defence: 0.0,
+    }
+  }
+}
+
+impl Stats {
+  pub fn get(&self, stat: Stat) -> f32 {
+    match stat {
+      Stat::Walk => self.walk,
+      Stat::Run => self.run,
+      Stat::Attack => self.attack,
+      Stat::Defence => self.defence,
+    }
+  }
+  pub fn get_mut(&mut self, stat: Stat) -> &mut f32 {
+    match stat {
+      Stat::Walk => &mut self.walk,
+      Stat::Run => &mut self.run,
+      Stat::Attack => &mut self.attack,
+      Stat::Defence => &mut self.defence,
+    }
+  }
+  /// Applies every additive modifier, then every multiplicative one.
+  pub fn with_modifiers<'a>(
+    &self,
+    modifiers: impl IntoIterator<Item = &'a StatModifier> + Clone,
+  ) -> Stats {
+    let mut stats = self.clone();
+    for m in modifiers.clone() {
+      if let Modifier::Add(value) = m.modifier {
+        *stats.get_mut(m.stat) += value;
+      }
+    }
+    for m in modifiers {
+      if let Modifier::Multiply(value) = m.modifier {
+        *stats.get_mut(m.stat) *= value;
+      }
+    }
+    stats
+  }
+}
+
+#[derive(Component, Clone, Debug, Default, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct BaseStats(pub Stats);
+
+/// The modifiers currently affecting a character, grouped by where they come
+/// from (e.g. `"equipment:weapon"`) so each source can be replaced on its own.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct StatModifiers(pub HashMap<String, Vec<StatModifier>>);
+
+impl StatModifiers {
+  pub fn iter(&self) -> impl Iterator<Item = &StatModifier> + Clone {
+    self.0.values().flatten()
+  }
+}
+
+#[derive(Bundle, Default)]
+pub struct StatsBundle {
+  pub base:      BaseStats,
+  pub modifiers: StatModifiers,
+  pub stats:     Stats,
+}
+
+#[allow(clippy::type_complexity)]
+fn recompute_stats(
+  mut query: Query<
+    (&BaseStats, &StatModifiers, &mut Stats),
+    Or<(Changed<BaseStats>, Changed<StatModifiers>)>,
+  >,
+) {
+  for (base, modifiers, mut stats) in query.iter_mut() {
+    *stats = base.0.with_modifiers(modifiers.iter());
+  }
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<Stats>()
+      .register_type::<BaseStats>()
+      .register_type::<StatModifiers>()
+      .add_systems(PostUpdate, recompute_stats);
+  }
+}
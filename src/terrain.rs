@@ -0,0 +1,245 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+  fog::{tile_cell, world_cell, Cell},
+  map::MapTile,
+  player::{movement::PlayerSpeeds, Player},
+  state::GameplaySet,
+  tile::{Tile, TilePosition},
+  vitals::Damage,
+};
+
+/// How often hurtful ground takes health, in seconds.
+const DAMAGE_INTERVAL: f32 = 0.5;
+
+/// What a footstep on the ground sounds like.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum FootstepMaterial {
+  #[default]
+  Grass,
+  Stone,
+  Ice,
+}
+
+/// How a kind of ground treats whoever walks on it.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Terrain {
+  /// Multiplies how fast players move across it.
+  pub speed:             f32,
+  /// From 0.0 for full grip to 1.0 for none. Slippery ground is slow to speed
+  /// up, stop and turn on.
+  pub slipperiness:      f32,
+  /// Health lost per second while standing on it.
+  pub damage_per_second: f32,
+  pub footstep:          FootstepMaterial,
+}
+
+impl Default for Terrain {
+  fn default() -> Self {
+    Terrain {
+      speed:             1.0,
+      slipperiness:      0.0,
+      damage_per_second: 0.0,
+      footstep:          FootstepMaterial::Grass,
+    }
+  }
+}
+
+impl Terrain {
+  /// `speeds` as they feel on this ground.
+  pub fn grip(&self, speeds: &PlayerSpeeds) -> PlayerSpeeds {
+    let grip = 1.0 - self.slipperiness.clamp(0.0, 1.0);
+    PlayerSpeeds {
+      acceleration: speeds.acceleration * grip,
+      deceleration: speeds.deceleration * grip,
+      turn_rate:    speeds.turn_rate * grip,
+    }
+  }
+}
+
+/// The ground tile in each cell, for looking up what's underfoot.
+#[derive(Resource, Default)]
+pub struct Ground(pub HashMap<Cell, MapTile>);
+
+impl Ground {
+  pub fn at(&self, world: Vec2) -> Option<&MapTile> {
+    self.0.get(&world_cell(world))
+  }
+  /// The terrain at a world position. Cells without ground act like grass.
+  pub fn terrain_at(&self, world: Vec2) -> Terrain {
+    self.at(world).map(MapTile::terrain).unwrap_or_default()
+  }
+}
+
+/// Rebuilds the ground when map tiles change.
+#[allow(clippy::type_complexity)]
+fn track_ground(
+  changed: Query<(), Changed<Tile<MapTile>>>,
+  mut removed: RemovedComponents<Tile<MapTile>>,
+  tiles: Query<(&TilePosition, &Tile<MapTile>)>,
+  mut ground: ResMut<Ground>,
+) {
+  if changed.is_empty() && removed.read().count() == 0 {
+    return;
+  }
+  ground.0 = tiles
+    .iter()
+    .filter(|(pos, _)| pos.layer == 0)
+    .map(|(pos, tile)| (tile_cell(pos), tile._type.clone()))
+    .collect();
+}
+
+/// Hurts players standing on hurtful ground, every `DAMAGE_INTERVAL`.
+fn terrain_damage(
+  players: Query<(Entity, &Transform), With<Player>>,
+  ground: Res<Ground>,
+  time: Res<Time>,
+  mut exposure: Local<HashMap<Entity, f32>>,
+  mut damage: EventWriter<Damage>,
+) {
+  for (entity, transform) in players.iter() {
+    let terrain = ground.terrain_at(transform.translation.xy());
+    if terrain.damage_per_second <= 0.0 {
+      exposure.remove(&entity);
+      continue;
+    }
+    let seconds = exposure.entry(entity).or_default();
+    *seconds += time.delta_seconds();
+    while *seconds >= DAMAGE_INTERVAL {
+      *seconds -= DAMAGE_INTERVAL;
+      damage.send(Damage {
+        entity,
+        amount: terrain.damage_per_second * DAMAGE_INTERVAL,
+      });
+    }
+  }
+}
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<Ground>()
+      .add_systems(Update, track_ground.before(crate::player::apply_movement))
+      .add_systems(
+        Update,
+        terrain_damage
+          .after(crate::player::apply_movement)
+          .in_set(GameplaySet),
+      );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use bevy::{ecs::event::ManualEventReader, time::TimeUpdateStrategy};
+
+  use super::*;
+  use crate::player::{
+    apply_movement, movement::Velocity, status::PlayerStatus,
+  };
+
+  const STEP: Duration = Duration::from_millis(50);
+  const WALK: Vec2 = Vec2::new(3.0, 0.0);
+
+  /// A player on a field of `tile`.
+  fn test_app(tile: MapTile) -> (App, Entity) {
+    let mut app = App::new();
+    app
+      .add_plugins(MinimalPlugins)
+      .insert_resource(TimeUpdateStrategy::ManualDuration(STEP))
+      .init_resource::<PlayerSpeeds>()
+      .insert_resource(Ground(
+        (-8..8)
+          .flat_map(|x| (-8..8).map(move |y| Cell::new(x, y)))
+          .map(|cell| (cell, tile.clone()))
+          .collect(),
+      ))
+      .add_event::<Damage>()
+      .add_systems(Update, (apply_movement, terrain_damage).chain());
+    let player = app
+      .world
+      .spawn((
+        Player(PlayerStatus::default()),
+        Velocity::default(),
+        Transform::default(),
+      ))
+      .id();
+    (app, player)
+  }
+
+  /// Runs `app` for `seconds` with the player set to `status`, returning how
+  /// far they moved and how many times they were hurt.
+  fn run(
+    app: &mut App,
+    player: Entity,
+    status: PlayerStatus,
+    seconds: f32,
+  ) -> (f32, usize) {
+    app.world.get_mut::<Player>(player).unwrap().0 = status;
+    let start = app.world.get::<Transform>(player).unwrap().translation;
+    let mut reader = ManualEventReader::<Damage>::default();
+    let mut hurt = 0;
+    for _ in 0..(seconds / STEP.as_secs_f32()).round() as usize {
+      app.update();
+      hurt += reader.read(app.world.resource::<Events<Damage>>()).count();
+    }
+    let end = app.world.get::<Transform>(player).unwrap().translation;
+    (end.distance(start), hurt)
+  }
+
+  /// How far a player walks east in a second, and then slides once they let
+  /// go.
+  fn walk_and_stop(tile: MapTile) -> (f32, f32) {
+    let (mut app, player) = test_app(tile);
+    let (walked, _) = run(&mut app, player, PlayerStatus::Walk(WALK), 1.0);
+    let stand = PlayerStatus::Stand(Default::default());
+    let (slid, _) = run(&mut app, player, stand, 2.0);
+    (walked, slid)
+  }
+
+  #[test]
+  fn tall_grass_slows_the_player() {
+    let (grass, _) = walk_and_stop(MapTile::Grass);
+    let (tall_grass, _) = walk_and_stop(MapTile::TallGrass);
+    assert!(grass > 2.0, "walked {grass} on grass");
+    assert!(
+      tall_grass < grass * 0.7,
+      "walked {tall_grass} on tall grass and {grass} on grass"
+    );
+  }
+
+  #[test]
+  fn flagstone_moves_like_grass() {
+    let (grass, grass_slide) = walk_and_stop(MapTile::Grass);
+    let (stone, stone_slide) = walk_and_stop(MapTile::Flagstone);
+    assert_eq!(grass, stone);
+    assert_eq!(grass_slide, stone_slide);
+  }
+
+  #[test]
+  fn ice_is_slow_to_get_going_and_to_stop() {
+    let (grass, grass_slide) = walk_and_stop(MapTile::Grass);
+    let (ice, ice_slide) = walk_and_stop(MapTile::Ice);
+    assert!(ice < grass, "walked {ice} on ice and {grass} on grass");
+    assert!(
+      ice_slide > grass_slide * 3.0,
+      "slid {ice_slide} on ice and {grass_slide} on grass"
+    );
+  }
+
+  #[test]
+  fn lava_hurts_while_standing_on_it() {
+    let stand = PlayerStatus::Stand(Default::default());
+    let (mut app, player) = test_app(MapTile::Lava);
+    let (moved, hurt) = run(&mut app, player, stand.clone(), 2.0);
+    assert_eq!(moved, 0.0);
+    assert!(hurt >= 3, "hurt {hurt} times in two seconds");
+
+    let (mut app, player) = test_app(MapTile::Grass);
+    assert_eq!(run(&mut app, player, stand, 2.0).1, 0);
+  }
+}
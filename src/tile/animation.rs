@@ -0,0 +1,232 @@
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::TileSheetCoords;
+use crate::loading::LoadingAssets;
+
+/// One frame of an animation clip.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationFrame {
+  /// The column and row of the frame in the tile's atlas.
+  pub coords:   (usize, usize),
+  #[serde(default)]
+  pub flip_x:   bool,
+  #[serde(default)]
+  pub flip_y:   bool,
+  /// How long this frame shows, in seconds. Falls back to the clip's
+  /// `frame_duration`.
+  #[serde(default)]
+  pub duration: Option<f32>,
+  /// Names of the `AnimationEvent`s sent when this frame starts showing.
+  #[serde(default)]
+  pub events:   Vec<String>,
+}
+
+impl AnimationFrame {
+  pub fn sheet_coords(&self) -> TileSheetCoords {
+    let mut coords = TileSheetCoords::new(self.coords.0, self.coords.1);
+    if self.flip_x {
+      coords = coords.flip_x();
+    }
+    if self.flip_y {
+      coords = coords.flip_y();
+    }
+    coords
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum LoopMode {
+  /// Starts over after the last frame.
+  #[default]
+  Loop,
+  /// Holds the last frame.
+  Once,
+}
+
+/// A named sequence of frames from one atlas.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AnimationClip {
+  pub frames:         Vec<AnimationFrame>,
+  #[serde(default)]
+  pub mode:           LoopMode,
+  /// How long frames without their own duration show, in seconds.
+  #[serde(default = "default_frame_duration")]
+  pub frame_duration: f32,
+}
+
+fn default_frame_duration() -> f32 { 1.0 }
+
+impl AnimationClip {
+  /// A looping clip of the given frames, all shown for the same time.
+  pub fn from_coords(coords: Vec<TileSheetCoords>, speed: Option<f32>) -> Self {
+    AnimationClip {
+      frames:         coords
+        .into_iter()
+        .map(|coords| AnimationFrame {
+          coords:   (coords.x, coords.y),
+          flip_x:   coords.flip_x,
+          flip_y:   coords.flip_y,
+          duration: None,
+          events:   Vec::new(),
+        })
+        .collect(),
+      mode:           LoopMode::Loop,
+      frame_duration: speed.unwrap_or(1.0).recip(),
+    }
+  }
+
+  /// The sheet coordinates of the frame `variant` lands on.
+  pub fn coords(&self, variant: usize) -> TileSheetCoords {
+    match self.frames.len() {
+      0 => TileSheetCoords::new(0, 0),
+      frames => self.frames[variant % frames].sheet_coords(),
+    }
+  }
+
+  /// How long `frame` shows, in seconds.
+  pub fn duration(&self, frame: usize) -> f32 {
+    self
+      .frames
+      .get(frame)
+      .and_then(|frame| frame.duration)
+      .unwrap_or(self.frame_duration)
+  }
+
+  /// How long one pass through every frame takes, in seconds.
+  pub fn total_duration(&self) -> f32 {
+    (0..self.frames.len())
+      .map(|frame| self.duration(frame))
+      .sum()
+  }
+
+  /// How many times the clip has got to its end by `time`. A clip that holds
+  /// its last frame only gets there once.
+  pub fn passes(&self, time: f32) -> usize {
+    let total = self.total_duration();
+    if self.frames.is_empty() || total <= 0.0 {
+      return 0;
+    }
+    let passes = (time / total).floor() as usize;
+    match self.mode {
+      LoopMode::Loop => passes,
+      LoopMode::Once => passes.min(1),
+    }
+  }
+
+  /// The number of frames shown from the start of the clip up to `time`,
+  /// counting every pass of a looping clip, minus one.
+  pub fn variant_at(&self, time: f32) -> usize {
+    let frames = self.frames.len();
+    let total = self.total_duration();
+    if frames == 0 || total <= 0.0 {
+      return 0;
+    }
+    let (passes, mut time) = match self.mode {
+      LoopMode::Loop => {
+        ((time / total).floor() as usize, time.rem_euclid(total))
+      }
+      LoopMode::Once if time >= total => return frames - 1,
+      LoopMode::Once => (0, time),
+    };
+    let mut frame = 0;
+    while frame + 1 < frames && time >= self.duration(frame) {
+      time -= self.duration(frame);
+      frame += 1;
+    }
+    passes * frames + frame
+  }
+}
+
+/// Every clip in an `.animations.ron` file, by name.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct AnimationLibrary {
+  pub clips: HashMap<String, AnimationClip>,
+}
+
+#[derive(Resource)]
+pub struct AnimationLibraryHandle(pub Handle<AnimationLibrary>);
+
+impl FromWorld for AnimationLibraryHandle {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    AnimationLibraryHandle(asset_server.load("data/base.animations.ron"))
+  }
+}
+
+#[derive(Debug, Error)]
+#[error(
+  "no animation {name:?} in the animation library (known clips: {known})"
+)]
+pub struct MissingAnimation {
+  pub name:  String,
+  pub known: String,
+}
+
+pub(super) fn track_animation_library(
+  library: Res<AnimationLibraryHandle>,
+  mut loading: ResMut<LoadingAssets>,
+) {
+  loading.add(library.0.clone().untyped());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A long first frame followed by two frames of the clip's 0.1 seconds.
+  fn long_first_frame(mode: LoopMode) -> AnimationClip {
+    AnimationClip {
+      frames: [Some(0.5), None, None]
+        .into_iter()
+        .map(|duration| AnimationFrame {
+          coords: (0, 0),
+          flip_x: false,
+          flip_y: false,
+          duration,
+          events: Vec::new(),
+        })
+        .collect(),
+      mode,
+      frame_duration: 0.1,
+    }
+  }
+
+  #[test]
+  fn frames_fall_back_to_the_clip_frame_duration() {
+    let clip = long_first_frame(LoopMode::Loop);
+    assert_eq!(clip.duration(0), 0.5);
+    assert_eq!(clip.duration(2), 0.1);
+    assert!((clip.total_duration() - 0.7).abs() < 1e-6);
+  }
+
+  #[test]
+  fn looping_clips_count_frames_across_passes() {
+    let clip = long_first_frame(LoopMode::Loop);
+    let variants = [0.0, 0.49, 0.55, 0.65, 0.75, 1.25, 1.35]
+      .map(|time| clip.variant_at(time));
+    assert_eq!(variants, [0, 0, 1, 2, 3, 4, 5]);
+    assert_eq!(clip.passes(0.65), 0);
+    assert_eq!(clip.passes(0.75), 1);
+    assert_eq!(clip.passes(1.5), 2);
+  }
+
+  #[test]
+  fn clips_played_once_hold_their_last_frame() {
+    let clip = long_first_frame(LoopMode::Once);
+    let variants = [0.0, 0.55, 0.65, 0.75, 5.0].map(|t| clip.variant_at(t));
+    assert_eq!(variants, [0, 1, 2, 2, 2]);
+    assert_eq!(clip.passes(0.65), 0);
+    assert_eq!(clip.passes(0.75), 1);
+    assert_eq!(clip.passes(5.0), 1);
+  }
+
+  #[test]
+  fn empty_clips_stay_on_the_first_frame() {
+    let clip = AnimationClip::default();
+    assert_eq!(clip.total_duration(), 0.0);
+    assert_eq!(clip.variant_at(3.0), 0);
+    assert_eq!(clip.passes(3.0), 0);
+  }
+}
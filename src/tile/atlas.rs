@@ -0,0 +1,220 @@
+use std::{
+  fmt::Display,
+  sync::{Arc, Mutex},
+};
+
+use bevy::{
+  prelude::*,
+  utils::{HashMap, HashSet},
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+  AnimationClip, AnimationLibrary, AnimationLibraryHandle, MissingAnimation,
+  TextureAtlasWithGrid,
+};
+use crate::loading::LoadingAssets;
+
+/// How to cut one texture into a grid of tiles.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AtlasDefinition {
+  /// The asset path of the texture.
+  pub texture: String,
+  /// The size of one cell, in pixels.
+  pub cell:    (f32, f32),
+  /// The number of columns and rows.
+  pub grid:    (usize, usize),
+  /// The gap between neighbouring cells, in pixels.
+  #[serde(default)]
+  pub padding: Option<(f32, f32)>,
+  /// The offset of the first cell from the top-left corner, in pixels.
+  #[serde(default)]
+  pub offset:  Option<(f32, f32)>,
+}
+
+/// Every atlas in an `.atlases.ron` file, by id.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct AtlasManifest {
+  pub atlases: HashMap<String, AtlasDefinition>,
+}
+
+#[derive(Resource)]
+pub struct AtlasManifestHandle(pub Handle<AtlasManifest>);
+
+impl FromWorld for AtlasManifestHandle {
+  fn from_world(world: &mut World) -> Self {
+    let asset_server = world.get_resource::<AssetServer>().unwrap();
+    AtlasManifestHandle(asset_server.load("data/base.atlases.ron"))
+  }
+}
+
+#[derive(Debug, Error)]
+#[error("no atlas {id:?} in the atlas manifest (known atlases: {known})")]
+pub struct MissingAtlas {
+  pub id:    String,
+  pub known: String,
+}
+
+/// The texture atlases declared in the atlas manifest, and the animations cut
+/// from them, built once both load.
+#[derive(Resource, Clone, Default)]
+pub struct TileAtlases {
+  atlases:      HashMap<String, TextureAtlasWithGrid>,
+  animations:   HashMap<String, AnimationClip>,
+  /// Every texture the atlases are cut from.
+  pub textures: Vec<Handle<Image>>,
+  /// Errors already logged by `report`.
+  reported:     Arc<Mutex<HashSet<String>>>,
+}
+
+impl TileAtlases {
+  pub fn get(&self, id: &str) -> Result<&TextureAtlasWithGrid, MissingAtlas> {
+    self.atlases.get(id).ok_or_else(|| {
+      let mut known = self.atlases.keys().cloned().collect::<Vec<_>>();
+      known.sort();
+      MissingAtlas {
+        id:    id.to_string(),
+        known: known.join(", "),
+      }
+    })
+  }
+
+  pub fn animation(
+    &self,
+    name: &str,
+  ) -> Result<&AnimationClip, MissingAnimation> {
+    self.animations.get(name).ok_or_else(|| {
+      let mut known = self.animations.keys().cloned().collect::<Vec<_>>();
+      known.sort();
+      MissingAnimation {
+        name:  name.to_string(),
+        known: known.join(", "),
+      }
+    })
+  }
+
+  /// Logs `err` the first time it comes up for these atlases, rather than for
+  /// every sprite cut from a missing atlas or clip on every frame.
+  pub fn report(&self, err: impl Display) {
+    let message = err.to_string();
+    if self.reported.lock().unwrap().insert(message.clone()) {
+      error!("{message}");
+    }
+  }
+
+  pub fn build(
+    manifest: &AtlasManifest,
+    library: &AnimationLibrary,
+    asset_server: &AssetServer,
+    texture_atlases: &mut Assets<TextureAtlas>,
+  ) -> Self {
+    let mut atlases = TileAtlases {
+      animations: library.clips.clone(),
+      ..default()
+    };
+    for (id, definition) in &manifest.atlases {
+      let texture: Handle<Image> = asset_server.load(&definition.texture);
+      let atlas = TextureAtlas::from_grid(
+        texture.clone(),
+        Vec2::from(definition.cell),
+        definition.grid.0,
+        definition.grid.1,
+        definition.padding.map(Vec2::from),
+        definition.offset.map(Vec2::from),
+      );
+      atlases.atlases.insert(id.clone(), TextureAtlasWithGrid {
+        atlas: texture_atlases.add(atlas),
+        grid:  definition.grid,
+      });
+      atlases.textures.push(texture);
+    }
+    atlases
+  }
+}
+
+pub(super) fn track_atlas_manifest(
+  manifest: Res<AtlasManifestHandle>,
+  mut loading: ResMut<LoadingAssets>,
+) {
+  loading.add(manifest.0.clone().untyped());
+}
+
+/// Builds the atlases as soon as the manifest and animations are available,
+/// and holds the loading screen until their textures are in.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_tile_atlases(
+  mut commands: Commands,
+  atlases: Option<Res<TileAtlases>>,
+  manifest: Res<AtlasManifestHandle>,
+  manifests: Res<Assets<AtlasManifest>>,
+  library: Res<AnimationLibraryHandle>,
+  libraries: Res<Assets<AnimationLibrary>>,
+  asset_server: Res<AssetServer>,
+  mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+  mut loading: ResMut<LoadingAssets>,
+) {
+  if atlases.is_some() {
+    return;
+  }
+  let (Some(manifest), Some(library)) =
+    (manifests.get(&manifest.0), libraries.get(&library.0))
+  else {
+    return;
+  };
+  let atlases =
+    TileAtlases::build(manifest, library, &asset_server, &mut texture_atlases);
+  for texture in &atlases.textures {
+    loading.add(texture.clone().untyped());
+  }
+  commands.insert_resource(atlases);
+}
+
+/// Rebuilds the atlases when the manifest, the animations or one of their
+/// textures changes on disk. Sprites pick the new atlases up through
+/// `refresh_tile_sprites`.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn reload_tile_atlases(
+  mut commands: Commands,
+  mut manifest_events: EventReader<AssetEvent<AtlasManifest>>,
+  mut library_events: EventReader<AssetEvent<AnimationLibrary>>,
+  mut image_events: EventReader<AssetEvent<Image>>,
+  atlases: Option<Res<TileAtlases>>,
+  manifest: Res<AtlasManifestHandle>,
+  manifests: Res<Assets<AtlasManifest>>,
+  library: Res<AnimationLibraryHandle>,
+  libraries: Res<Assets<AnimationLibrary>>,
+  asset_server: Res<AssetServer>,
+  mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+) {
+  let data_modified = manifest_events
+    .read()
+    .any(|event| event.is_modified(&manifest.0))
+    | library_events
+      .read()
+      .any(|event| event.is_modified(&library.0));
+  let Some(atlases) = atlases else {
+    return;
+  };
+  let texture_modified = image_events.read().any(|event| {
+    atlases
+      .textures
+      .iter()
+      .any(|texture| event.is_modified(texture))
+  });
+  if !data_modified && !texture_modified {
+    return;
+  }
+  let (Some(manifest), Some(library)) =
+    (manifests.get(&manifest.0), libraries.get(&library.0))
+  else {
+    return;
+  };
+  info!("reloading tile atlases");
+  commands.insert_resource(TileAtlases::build(
+    manifest,
+    library,
+    &asset_server,
+    &mut texture_atlases,
+  ));
+}
@@ -1,4 +1,6 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Copy, Reflect, PartialEq, Eq, Default)]
 pub enum Direction4 {
   North,
@@ -32,7 +34,30 @@ impl TryFrom<Vec2> for Direction4 {
   }
 }
 
-#[derive(Clone, Copy, Reflect, PartialEq, Eq, Default)]
+impl Direction4 {
+  /// The unit vector pointing this way.
+  pub fn as_vec2(&self) -> Vec2 {
+    match self {
+      Direction4::North => Vec2::Y,
+      Direction4::East => Vec2::X,
+      Direction4::South => Vec2::NEG_Y,
+      Direction4::West => Vec2::NEG_X,
+    }
+  }
+  /// The lowercase name, as used in animation clip names.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Direction4::North => "north",
+      Direction4::East => "east",
+      Direction4::South => "south",
+      Direction4::West => "west",
+    }
+  }
+}
+
+#[derive(
+  Clone, Copy, Debug, Reflect, PartialEq, Eq, Default, Serialize, Deserialize,
+)]
 pub enum Direction8 {
   North,
   NorthEast,
@@ -45,7 +70,99 @@ pub enum Direction8 {
   NorthWest,
 }
 
-#[derive(Clone, Copy, Reflect)]
+impl TryFrom<Vec2> for Direction8 {
+  type Error = ();
+
+  fn try_from(value: Vec2) -> Result<Self, Self::Error> {
+    if value.normalize_or_zero() == Vec2::ZERO {
+      return Err(());
+    }
+    // eighths of a turn clockwise from north, rounded to the nearest
+    let angle = value.x.atan2(value.y);
+    let eighth = (angle / std::f32::consts::FRAC_PI_4).round() as i32;
+    Ok(Direction8::ALL[eighth.rem_euclid(8) as usize])
+  }
+}
+
+impl From<Direction4> for Direction8 {
+  fn from(value: Direction4) -> Self {
+    match value {
+      Direction4::North => Direction8::North,
+      Direction4::East => Direction8::East,
+      Direction4::South => Direction8::South,
+      Direction4::West => Direction8::West,
+    }
+  }
+}
+
+/// Diagonals become vertical directions, like `Direction4::try_from`.
+impl From<Direction8> for Direction4 {
+  fn from(value: Direction8) -> Self {
+    match value {
+      Direction8::North | Direction8::NorthEast | Direction8::NorthWest => {
+        Direction4::North
+      }
+      Direction8::East => Direction4::East,
+      Direction8::South | Direction8::SouthEast | Direction8::SouthWest => {
+        Direction4::South
+      }
+      Direction8::West => Direction4::West,
+    }
+  }
+}
+
+impl Direction8 {
+  pub const ALL: [Direction8; 8] = [
+    Direction8::North,
+    Direction8::NorthEast,
+    Direction8::East,
+    Direction8::SouthEast,
+    Direction8::South,
+    Direction8::SouthWest,
+    Direction8::West,
+    Direction8::NorthWest,
+  ];
+
+  /// The unit vector pointing this way.
+  pub fn as_vec2(&self) -> Vec2 {
+    match self {
+      Direction8::North => Vec2::Y,
+      Direction8::NorthEast => Vec2::ONE.normalize(),
+      Direction8::East => Vec2::X,
+      Direction8::SouthEast => Vec2::new(1.0, -1.0).normalize(),
+      Direction8::South => Vec2::NEG_Y,
+      Direction8::SouthWest => Vec2::NEG_ONE.normalize(),
+      Direction8::West => Vec2::NEG_X,
+      Direction8::NorthWest => Vec2::new(-1.0, 1.0).normalize(),
+    }
+  }
+  pub fn is_diagonal(&self) -> bool {
+    matches!(
+      self,
+      Direction8::NorthEast
+        | Direction8::SouthEast
+        | Direction8::SouthWest
+        | Direction8::NorthWest
+    )
+  }
+  /// The lowercase name, as used in animation clip names.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Direction8::North => "north",
+      Direction8::NorthEast => "north_east",
+      Direction8::East => "east",
+      Direction8::SouthEast => "south_east",
+      Direction8::South => "south",
+      Direction8::SouthWest => "south_west",
+      Direction8::West => "west",
+      Direction8::NorthWest => "north_west",
+    }
+  }
+}
+
+#[derive(
+  Clone, Copy, Debug, Reflect, PartialEq, Eq, Serialize, Deserialize,
+)]
 pub enum VerticalPart {
   Top,
   Bottom,
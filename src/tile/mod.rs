@@ -1,16 +1,61 @@
+mod animation;
+mod atlas;
 mod ident;
 
+use std::borrow::Cow;
+
 use bevy::prelude::*;
 
-pub use self::ident::*;
+// named outright so it wins over the `AnimationClip` in bevy's prelude
+pub use self::animation::AnimationClip;
+pub use self::{animation::*, atlas::*, ident::*};
+use crate::{data::RonAssetLoader, loading::check_loading, state::AppState};
 
 pub trait TileType: Reflect {
   /// The number of pixels in this tile that equals one world unit, and the the
   /// offset in world units required to center this tile.
   fn size_and_center(&self) -> (Vec2, Vec2);
-  fn coords(&self) -> Vec<TileSheetCoords>;
-  fn atlas_handle(&self, atlases: &TileAtlases) -> TextureAtlasWithGrid;
+  /// The frames of tiles that aren't drawn from a named animation.
+  fn coords(&self) -> Vec<TileSheetCoords> { Vec::new() }
+  /// The id of the atlas, in the atlas manifest, this tile is cut from.
+  fn atlas_id(&self) -> &str;
+  fn atlas_handle(&self, atlases: &TileAtlases) -> TextureAtlasWithGrid {
+    match atlases.get(self.atlas_id()) {
+      Ok(atlas) => atlas.clone(),
+      Err(err) => {
+        atlases.report(err);
+        TextureAtlasWithGrid::default()
+      }
+    }
+  }
   fn anim_speed(&self) -> Option<f32> { None }
+  /// The names of the clips in the animation library this tile can be drawn
+  /// from instead of `coords` and `anim_speed`, best first. The first one in
+  /// the library is used, so sheets can leave out frames they don't have.
+  fn animations(&self) -> Vec<String> { Vec::new() }
+  /// The frames this tile cycles through and how long each shows.
+  fn clip<'a>(&self, atlases: &'a TileAtlases) -> Cow<'a, AnimationClip> {
+    let names = self.animations();
+    if let Some(clip) =
+      names.iter().find_map(|name| atlases.animation(name).ok())
+    {
+      return Cow::Borrowed(clip);
+    }
+    if let Some(Err(err)) = names.first().map(|name| atlases.animation(name)) {
+      atlases.report(err);
+    }
+    let mut clip = AnimationClip::from_coords(self.coords(), self.anim_speed());
+    for (frame, name) in self.frame_events() {
+      if let Some(frame) = clip.frames.get_mut(frame) {
+        frame.events.push(name.to_string());
+      }
+    }
+    Cow::Owned(clip)
+  }
+  /// Named events fired when a clip built from `coords` reaches a frame, as
+  /// pairs of frame index into the clip and name. Clips from the animation
+  /// library name their events on their frames instead.
+  fn frame_events(&self) -> Vec<(usize, &'static str)> { Vec::new() }
 }
 
 pub fn rect_range(
@@ -44,15 +89,14 @@ pub struct Tile<Ty: TileType> {
 
 impl<Ty: TileType> Tile<Ty> {
   pub fn new(_type: Ty) -> Self { Self { _type, variant: 0 } }
-  pub fn coords(&self) -> TileSheetCoords {
-    let all_variants = self._type.coords();
-    all_variants[self.variant % all_variants.len()].clone()
+  pub fn coords(&self, atlases: &TileAtlases) -> TileSheetCoords {
+    self._type.clip(atlases).coords(self.variant)
   }
   pub fn texture_atlas_sprite(
     &self,
     atlases: &TileAtlases,
   ) -> TextureAtlasSprite {
-    let coords = self.coords();
+    let coords = self.coords(atlases);
     let atlas = self._type.atlas_handle(atlases);
     atlas.texture_atlas_sprite(coords)
   }
@@ -62,14 +106,138 @@ impl<Ty: TileType> Tile<Ty> {
 pub struct AnimatedTile<Ty: TileType> {
   pub tile: Tile<Ty>,
   pub time: f32,
+  /// The tile's clip, looked up once when the tile is made rather than on
+  /// every tick.
+  #[reflect(ignore)]
+  clip:     AnimationClip,
 }
 
 impl<Ty: TileType> AnimatedTile<Ty> {
-  pub fn new(tile: Tile<Ty>) -> Self { Self { tile, time: 0.0 } }
+  pub fn new(tile: Tile<Ty>, atlases: &TileAtlases) -> Self {
+    let clip = tile._type.clip(atlases).into_owned();
+    Self {
+      tile,
+      time: 0.0,
+      clip,
+    }
+  }
   pub fn anim_speed(&self) -> Option<f32> { self.tile._type.anim_speed() }
-  pub fn tick(&mut self, delta: f32) {
+  pub fn clip(&self) -> &AnimationClip { &self.clip }
+  /// Looks the clip up again, for when the atlases are rebuilt.
+  pub fn refresh_clip(&mut self, atlases: &TileAtlases) {
+    self.clip = self.tile._type.clip(atlases).into_owned();
+  }
+  /// The index into the clip of the frame showing.
+  pub fn frame(&self) -> usize {
+    self.tile.variant % self.clip.frames.len().max(1)
+  }
+  /// The names of the events on `frame`.
+  pub fn events_at(&self, frame: usize) -> Vec<String> {
+    self
+      .clip
+      .frames
+      .get(frame)
+      .map(|frame| frame.events.clone())
+      .unwrap_or_default()
+  }
+  /// The same animation moved on to `_type`, as far through its clip as this
+  /// one is through its own, so a walk can speed up into a run without
+  /// restarting.
+  pub fn continue_as(&self, _type: Ty, atlases: &TileAtlases) -> Self {
+    let before = self.clip.total_duration();
+    let mut tile = AnimatedTile::new(Tile::new(_type), atlases);
+    if before > 0.0 {
+      tile.time = self.time / before * tile.clip.total_duration();
+    }
+    tile.tile.variant = tile.clip.variant_at(tile.time);
+    tile
+  }
+  /// Advances the animation, returning the events of every frame it reached
+  /// on the way, in order, and whether the clip got to its end.
+  pub fn tick(&mut self, delta: f32) -> AnimationProgress {
+    let before = self.tile.variant;
+    let passes = self.clip.passes(self.time);
     self.time += delta;
-    self.tile.variant = (self.time * self.anim_speed().unwrap_or(1.0)) as usize;
+    self.tile.variant = self.clip.variant_at(self.time);
+    let frames = self.clip.frames.len().max(1);
+    // a long frame can skip some, but never more than a whole cycle
+    let start = (before + 1).max(self.tile.variant.saturating_sub(frames - 1));
+    AnimationProgress {
+      events:    (start..=self.tile.variant)
+        .flat_map(|variant| self.events_at(variant % frames))
+        .collect(),
+      completed: self.clip.passes(self.time) > passes,
+    }
+  }
+  pub fn texture_atlas_sprite(
+    &self,
+    atlases: &TileAtlases,
+  ) -> TextureAtlasSprite {
+    let atlas = self.tile._type.atlas_handle(atlases);
+    atlas.texture_atlas_sprite(self.clip.coords(self.tile.variant))
+  }
+}
+
+/// What happened to an animation during one `AnimatedTile::tick`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnimationProgress {
+  /// The frame events reached, in order.
+  pub events:    Vec<String>,
+  /// Whether the last frame finished: at the end of every pass of a looping
+  /// clip, and once for a clip that holds its last frame.
+  pub completed: bool,
+}
+
+/// One of the events named on a frame of an animated tile's clip, sent when
+/// the frame starts showing.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct AnimationEvent {
+  pub entity: Entity,
+  pub name:   String,
+}
+
+/// Sent when an animated tile's clip gets to its end.
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct AnimationComplete {
+  pub entity: Entity,
+}
+
+/// Re-cuts the sprite of every `Tile<Ty>` from the current atlases, keeping
+/// its tint. Run whenever the atlases are rebuilt.
+pub fn refresh_tile_sprites<Ty: TileType>(
+  atlases: Res<TileAtlases>,
+  mut tiles: Query<(
+    &Tile<Ty>,
+    &mut Handle<TextureAtlas>,
+    &mut TextureAtlasSprite,
+  )>,
+) {
+  for (tile, mut atlas, mut sprite) in tiles.iter_mut() {
+    *atlas = tile._type.atlas_handle(&atlases).atlas;
+    *sprite = TextureAtlasSprite {
+      color: sprite.color,
+      ..tile.texture_atlas_sprite(&atlases)
+    };
+  }
+}
+
+/// Like `refresh_tile_sprites`, for animated tiles, which also pick up their
+/// clips again.
+pub fn refresh_animated_tile_sprites<Ty: TileType>(
+  atlases: Res<TileAtlases>,
+  mut tiles: Query<(
+    &mut AnimatedTile<Ty>,
+    &mut Handle<TextureAtlas>,
+    &mut TextureAtlasSprite,
+  )>,
+) {
+  for (mut tile, mut atlas, mut sprite) in tiles.iter_mut() {
+    tile.refresh_clip(&atlases);
+    *atlas = tile.tile._type.atlas_handle(&atlases).atlas;
+    *sprite = TextureAtlasSprite {
+      color: sprite.color,
+      ..tile.texture_atlas_sprite(&atlases)
+    };
   }
 }
 
@@ -119,7 +287,7 @@ impl TilePosition {
   pub fn new(x: i64, y: i64, layer: u8) -> Self { Self { x, y, layer } }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct TextureAtlasWithGrid {
   pub atlas: Handle<TextureAtlas>,
   grid:      (usize, usize),
@@ -142,77 +310,155 @@ impl TextureAtlasWithGrid {
   }
 }
 
-#[derive(Resource, Clone)]
-pub struct TileAtlases {
-  pub grass:       TextureAtlasWithGrid,
-  pub wall:        TextureAtlasWithGrid,
-  pub player_base: TextureAtlasWithGrid,
+pub struct TilePlugin;
+
+impl Plugin for TilePlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .add_event::<AnimationEvent>()
+      .add_event::<AnimationComplete>()
+      .init_asset::<AtlasManifest>()
+      .init_asset::<AnimationLibrary>()
+      .register_asset_loader(RonAssetLoader::<AtlasManifest>::new(&[
+        "atlases.ron",
+      ]))
+      .register_asset_loader(RonAssetLoader::<AnimationLibrary>::new(&[
+        "animations.ron",
+      ]))
+      .init_resource::<AtlasManifestHandle>()
+      .init_resource::<AnimationLibraryHandle>()
+      .add_systems(
+        OnEnter(AppState::Loading),
+        (track_atlas_manifest, track_animation_library),
+      )
+      .add_systems(
+        Update,
+        (
+          build_tile_atlases
+            .before(check_loading)
+            .run_if(in_state(AppState::Loading)),
+          reload_tile_atlases,
+        ),
+      );
+  }
 }
 
-impl FromWorld for TileAtlases {
-  fn from_world(world: &mut World) -> Self {
-    let asset_server = world.get_resource::<AssetServer>().unwrap();
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-    let grass_texture_handle = asset_server.load("textures/tiles/grass.png");
-    let wall_texture_handle = asset_server.load("textures/tiles/wall.png");
-    let player_base_texture_handle =
-      asset_server.load("textures/player/fbas_1body_human_00.png");
+  /// Four frames from coords, with a foot coming down on the first and third.
+  #[derive(Reflect)]
+  enum Gait {
+    Walk,
+    Run,
+  }
 
-    let mut texture_atlases =
-      world.get_resource_mut::<Assets<TextureAtlas>>().unwrap();
+  impl TileType for Gait {
+    fn size_and_center(&self) -> (Vec2, Vec2) {
+      (Vec2::splat(16.0), Vec2::ZERO)
+    }
+    fn coords(&self) -> Vec<TileSheetCoords> {
+      (0..4).map(|x| TileSheetCoords::new(x, 0)).collect()
+    }
+    fn atlas_id(&self) -> &str { "gait" }
+    fn anim_speed(&self) -> Option<f32> {
+      match self {
+        Gait::Walk => Some(4.0),
+        Gait::Run => Some(8.0),
+      }
+    }
+    fn frame_events(&self) -> Vec<(usize, &'static str)> {
+      vec![(0, "footstep"), (2, "footstep")]
+    }
+  }
 
-    let grass_atlas = TextureAtlas::from_grid(
-      grass_texture_handle,
-      Vec2::new(32.0, 32.0),
-      8,
-      8,
-      None,
-      None,
-    );
-    let grass_atlas_handle = texture_atlases.add(grass_atlas);
-    let grass_atlas = TextureAtlasWithGrid {
-      atlas: grass_atlas_handle,
-      grid:  (8, 8),
+  /// A walk whose clip is a long first frame followed by two short ones,
+  /// each naming an event.
+  fn long_first_frame(mode: LoopMode) -> AnimatedTile<Gait> {
+    let mut tile =
+      AnimatedTile::new(Tile::new(Gait::Walk), &TileAtlases::default());
+    tile.clip = AnimationClip {
+      frames: [(Some(0.5), "a"), (None, "b"), (None, "c")]
+        .into_iter()
+        .map(|(duration, event)| AnimationFrame {
+          coords: (0, 0),
+          flip_x: false,
+          flip_y: false,
+          duration,
+          events: vec![event.to_string()],
+        })
+        .collect(),
+      mode,
+      frame_duration: 0.1,
     };
+    tile
+  }
 
-    let wall_atlas = TextureAtlas::from_grid(
-      wall_texture_handle,
-      Vec2::new(32.0, 32.0),
-      14,
-      10,
-      None,
-      None,
-    );
-    let wall_atlas_handle = texture_atlases.add(wall_atlas);
-    let wall_atlas = TextureAtlasWithGrid {
-      atlas: wall_atlas_handle,
-      grid:  (14, 10),
-    };
+  fn progress(events: &[&str], completed: bool) -> AnimationProgress {
+    AnimationProgress {
+      events: events.iter().map(|event| event.to_string()).collect(),
+      completed,
+    }
+  }
 
-    let player_base_atlas = TextureAtlas::from_grid(
-      player_base_texture_handle,
-      Vec2::new(64.0, 64.0),
-      16,
-      16,
-      None,
-      None,
-    );
-    let player_base_atlas_handle = texture_atlases.add(player_base_atlas);
-    let player_base_atlas = TextureAtlasWithGrid {
-      atlas: player_base_atlas_handle,
-      grid:  (16, 16),
-    };
+  #[test]
+  fn a_long_first_frame_holds_before_the_short_ones() {
+    let mut tile = long_first_frame(LoopMode::Loop);
+    assert_eq!(tile.tick(0.3), progress(&[], false));
+    assert_eq!(tile.frame(), 0);
+    assert_eq!(tile.tick(0.25), progress(&["b"], false));
+    assert_eq!(tile.tick(0.1), progress(&["c"], false));
+    assert_eq!(tile.tick(0.1), progress(&["a"], true));
+    assert_eq!(tile.frame(), 0);
+  }
 
-    TileAtlases {
-      grass:       grass_atlas,
-      wall:        wall_atlas,
-      player_base: player_base_atlas,
-    }
+  #[test]
+  fn skipped_frames_still_send_their_events() {
+    let mut tile = long_first_frame(LoopMode::Loop);
+    assert_eq!(tile.tick(0.65), progress(&["b", "c"], false));
+    // ten passes in one tick send one pass's worth
+    assert_eq!(tile.tick(7.0), progress(&["a", "b", "c"], true));
+    assert_eq!(tile.frame(), 2);
   }
-}
 
-pub struct TilePlugin;
+  #[test]
+  fn a_clip_played_once_holds_its_last_frame_and_completes_once() {
+    let mut tile = long_first_frame(LoopMode::Once);
+    assert_eq!(tile.tick(0.65), progress(&["b", "c"], false));
+    assert_eq!(tile.tick(0.1), progress(&[], true));
+    assert_eq!(tile.tick(1.0), progress(&[], false));
+    assert_eq!(tile.frame(), 2);
 
-impl Plugin for TilePlugin {
-  fn build(&self, app: &mut App) { app.init_resource::<TileAtlases>(); }
+    let mut tile = long_first_frame(LoopMode::Once);
+    assert_eq!(tile.tick(5.0), progress(&["b", "c"], true));
+    assert_eq!(tile.tick(5.0), progress(&[], false));
+  }
+
+  #[test]
+  fn coord_clips_take_their_events_from_the_tile_type() {
+    let mut tile =
+      AnimatedTile::new(Tile::new(Gait::Walk), &TileAtlases::default());
+    assert_eq!(tile.events_at(0), ["footstep"]);
+    assert_eq!(tile.tick(0.3), progress(&[], false));
+    assert_eq!(tile.tick(0.3), progress(&["footstep"], false));
+    assert_eq!(tile.tick(0.5), progress(&["footstep"], true));
+  }
+
+  #[test]
+  fn a_walk_carries_its_phase_over_into_a_run() {
+    let atlases = TileAtlases::default();
+    let mut walk = AnimatedTile::new(Tile::new(Gait::Walk), &atlases);
+    walk.tick(0.6);
+    assert_eq!(walk.frame(), 2);
+
+    let mut run = walk.continue_as(Gait::Run, &atlases);
+    assert!(matches!(run.tile._type, Gait::Run));
+    assert!((run.time - 0.3).abs() < 1e-6);
+    assert_eq!(run.frame(), 2);
+    // the run goes on from there rather than starting over
+    assert_eq!(run.tick(0.1), progress(&[], false));
+    assert_eq!(run.frame(), 3);
+    assert_eq!(run.tick(0.1), progress(&["footstep"], true));
+  }
 }
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::{
+  player::{status::PlayerStatus, Player},
+  state::{AppState, GameplaySet},
+};
+
+/// How much stamina comes back per second while not running.
+const STAMINA_REGEN: f32 = 0.5;
+
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct Health {
+  pub current: f32,
+  pub max:     f32,
+}
+
+impl Default for Health {
+  fn default() -> Self {
+    Health {
+      current: 10.0,
+      max:     10.0,
+    }
+  }
+}
+
+/// Seconds of running left.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct Stamina {
+  pub current: f32,
+  pub max:     f32,
+}
+
+impl Default for Stamina {
+  fn default() -> Self {
+    Stamina {
+      current: 5.0,
+      max:     5.0,
+    }
+  }
+}
+
+impl Health {
+  pub fn fraction(&self) -> f32 { (self.current / self.max).clamp(0.0, 1.0) }
+  pub fn is_dead(&self) -> bool { self.current <= 0.0 }
+}
+
+impl Stamina {
+  pub fn fraction(&self) -> f32 { (self.current / self.max).clamp(0.0, 1.0) }
+}
+
+/// Takes health from an entity. Negative amounts heal.
+#[derive(Event, Clone, Debug)]
+pub struct Damage {
+  pub entity: Entity,
+  pub amount: f32,
+}
+
+fn apply_damage(
+  mut events: EventReader<Damage>,
+  mut query: Query<&mut Health>,
+) {
+  for event in events.read() {
+    let Ok(mut health) = query.get_mut(event.entity) else {
+      continue;
+    };
+    health.current = (health.current - event.amount).clamp(0.0, health.max);
+  }
+}
+
+/// Drains stamina while running and refills it otherwise.
+fn update_stamina(mut query: Query<(&Player, &mut Stamina)>, time: Res<Time>) {
+  for (player, mut stamina) in query.iter_mut() {
+    let delta = match player.0 {
+      PlayerStatus::Run(_) => -time.delta_seconds(),
+      _ => time.delta_seconds() * STAMINA_REGEN,
+    };
+    let current = (stamina.current + delta).clamp(0.0, stamina.max);
+    if current != stamina.current {
+      stamina.current = current;
+    }
+  }
+}
+
+/// Ends the run once every player is down.
+fn check_game_over(
+  players: Query<&Health, With<Player>>,
+  mut next: ResMut<NextState<AppState>>,
+) {
+  if !players.is_empty() && players.iter().all(Health::is_dead) {
+    next.set(AppState::GameOver);
+  }
+}
+
+pub struct VitalsPlugin;
+
+impl Plugin for VitalsPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .register_type::<Health>()
+      .register_type::<Stamina>()
+      .add_event::<Damage>()
+      .add_systems(
+        Update,
+        (
+          apply_damage,
+          update_stamina.after(crate::player::apply_movement),
+          check_game_over.after(apply_damage),
+        )
+          .in_set(GameplaySet),
+      );
+  }
+}